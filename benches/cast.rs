@@ -0,0 +1,91 @@
+use std::f32::consts::PI;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use raycaster::cast::{self, Camera};
+use raycaster::map::{self, Map};
+
+const MAP_SIZES: [u32; 3] = [512, 1024, 2048];
+
+fn poses() -> [(&'static str, Camera); 3] {
+    [
+        // long look down the spooky map's corridor
+        (
+            "corridor",
+            Camera {
+                x: 240,
+                y: 464,
+                radians: 0.5 * PI,
+                ..Camera::default()
+            },
+        ),
+        // nose up against the outer wall
+        (
+            "wall",
+            Camera {
+                x: 240,
+                y: 464,
+                radians: 1.5 * PI,
+                ..Camera::default()
+            },
+        ),
+        // diagonal across a room
+        (
+            "diagonal",
+            Camera {
+                x: 96,
+                y: 448,
+                radians: 0.25 * PI,
+                ..Camera::default()
+            },
+        ),
+    ]
+}
+
+fn open_camera(map: &Map) -> Camera {
+    // bottom right corner of gen_map is open at every size we bench
+    Camera {
+        x: map.w as i32 - 64,
+        y: map.h as i32 - 64,
+        radians: 0.75 * PI,
+        ..Camera::default()
+    }
+}
+
+fn cast_ray(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cast_ray");
+    let spooky = map::spooky_map();
+    for (name, cam) in poses() {
+        group.bench_with_input(BenchmarkId::new("pose", name), &cam, |b, cam| {
+            b.iter(|| cast::cast_ray(black_box(&spooky), black_box(cam), 0.5))
+        });
+    }
+    for size in MAP_SIZES {
+        let map = map::gen_map(size, size);
+        let cam = open_camera(&map);
+        group.bench_with_input(BenchmarkId::new("map", size), &map, |b, map| {
+            b.iter(|| cast::cast_ray(black_box(map), black_box(&cam), 0.5))
+        });
+    }
+    group.finish();
+}
+
+fn cast_fov(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cast_fov");
+    let spooky = map::spooky_map();
+    for (name, cam) in poses() {
+        group.bench_with_input(BenchmarkId::new("pose", name), &cam, |b, cam| {
+            b.iter(|| cast::cast_fov(black_box(&spooky), black_box(cam)))
+        });
+    }
+    for size in MAP_SIZES {
+        let map = map::gen_map(size, size);
+        let cam = open_camera(&map);
+        group.bench_with_input(BenchmarkId::new("map", size), &map, |b, map| {
+            b.iter(|| cast::cast_fov(black_box(map), black_box(&cam)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cast_ray, cast_fov);
+criterion_main!(benches);
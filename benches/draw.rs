@@ -0,0 +1,49 @@
+use std::f32::consts::PI;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use raycaster::cast::{self, Camera};
+use raycaster::{draw, map};
+
+const RESOLUTIONS: [(u32, u32); 3] = [(512, 512), (1280, 720), (1920, 1080)];
+const MAP_SIZES: [u32; 3] = [512, 1024, 2048];
+
+fn camera(radians: f32) -> Camera {
+    Camera {
+        x: 240,
+        y: 464,
+        radians,
+        ..Camera::default()
+    }
+}
+
+fn draw_view(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw_view");
+    let map = map::spooky_map();
+    for (w, h) in RESOLUTIONS {
+        for (name, radians) in [("corridor", 0.5 * PI), ("wall", 1.5 * PI)] {
+            let cam = camera(radians);
+            let view = cast::cast_columns(&map, &cam, w);
+            let mut img = image::RgbImage::new(w, h);
+            let id = BenchmarkId::new(name, format!("{w}x{h}"));
+            group.bench_with_input(id, &view, |b, view| {
+                b.iter(|| draw::draw_view(&mut img, black_box(view), &cam))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn draw_map(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw_map");
+    for size in MAP_SIZES {
+        let map = map::gen_map(size, size);
+        let mut img = image::RgbImage::new(size, size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &map, |b, map| {
+            b.iter(|| draw::draw_map(&mut img, black_box(map)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, draw_view, draw_map);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+// Full frame at 1080p. Compare against the threaded path with:
+//   cargo bench --bench frame
+//   cargo bench --bench frame --features parallel
+use std::f32::consts::PI;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use raycaster::cast::{self, Camera};
+use raycaster::{draw, map};
+
+const W: u32 = 1920;
+const H: u32 = 1080;
+
+fn frame(c: &mut Criterion) {
+    let map = map::spooky_map();
+    let cam = Camera {
+        x: 240,
+        y: 464,
+        radians: 0.5 * PI,
+        ..Camera::default()
+    };
+    let view = cast::cast_columns(&map, &cam, W);
+    let mut img = image::RgbImage::new(W, H);
+
+    c.bench_function("cast_columns 1080p", |b| {
+        b.iter(|| cast::cast_columns(black_box(&map), black_box(&cam), W))
+    });
+    c.bench_function("draw_view 1080p", |b| {
+        b.iter(|| draw::draw_view(&mut img, black_box(&view), &cam))
+    });
+    c.bench_function("cast + draw 1080p", |b| {
+        b.iter(|| {
+            let view = cast::cast_columns(black_box(&map), black_box(&cam), W);
+            draw::draw_view(&mut img, &view, &cam);
+        })
+    });
+}
+
+criterion_group!(benches, frame);
+criterion_main!(benches);
@@ -0,0 +1,394 @@
+// Monsters and other NPCs: they walk a patrol, notice the player when they
+// have a clear line of sight, and chase along A* paths.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::f32::consts::PI;
+
+use crate::cast::Camera;
+use crate::map::{Map, Thing};
+use crate::sight;
+use crate::sprite::{Billboard, Directional};
+
+/// Which coarse cells of a map can be walked through, for pathfinding.
+/// Build it again after the map changes.
+pub struct NavGrid {
+    /// Cell size in map pixels.
+    pub cell: u32,
+    pub w: u32,
+    pub h: u32,
+    open: Vec<bool>,
+}
+
+impl NavGrid {
+    /// A cell is open when none of its pixels are walls or under a
+    /// segment. Glass and grates count as walls here, they just don't
+    /// block sight.
+    pub fn new(map: &Map, cell: u32) -> Self {
+        let cell = cell.max(1);
+        let (w, h) = (map.w.div_ceil(cell), map.h.div_ceil(cell));
+        let mut open = vec![true; (w * h) as usize];
+        let blocked = map.blocked();
+        for y in 0..map.h {
+            for x in 0..map.w {
+                if blocked[(x + y * map.w) as usize] {
+                    open[(x / cell + y / cell * w) as usize] = false;
+                }
+            }
+        }
+        Self { cell, w, h, open }
+    }
+
+    fn cell_at(&self, (x, y): (i32, i32)) -> Option<(u32, u32)> {
+        let (cx, cy) = (
+            x.div_euclid(self.cell as i32),
+            y.div_euclid(self.cell as i32),
+        );
+        (cx >= 0 && cy >= 0 && (cx as u32) < self.w && (cy as u32) < self.h)
+            .then_some((cx as u32, cy as u32))
+    }
+
+    pub fn is_open(&self, cx: u32, cy: u32) -> bool {
+        cx < self.w && cy < self.h && self.open[(cx + cy * self.w) as usize]
+    }
+
+    fn centre(&self, cx: u32, cy: u32) -> (i32, i32) {
+        let half = self.cell / 2;
+        (
+            (cx * self.cell + half) as i32,
+            (cy * self.cell + half) as i32,
+        )
+    }
+
+    /// Waypoints in map pixels from `from` to `to`, through the centres of
+    /// open cells and ending at `to`. `None` if there's no way there.
+    pub fn find_path(&self, from: (i32, i32), to: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+        let start = self.cell_at(from)?;
+        let goal = self.cell_at(to)?;
+        if !self.is_open(goal.0, goal.1) {
+            return None;
+        }
+        let idx = |(cx, cy): (u32, u32)| (cx + cy * self.w) as usize;
+        let guess = |(cx, cy): (u32, u32)| cx.abs_diff(goal.0) + cy.abs_diff(goal.1);
+        let mut cost = vec![u32::MAX; self.open.len()];
+        let mut came_from = vec![usize::MAX; self.open.len()];
+        let mut queue = BinaryHeap::new();
+        cost[idx(start)] = 0;
+        queue.push(Reverse((guess(start), idx(start))));
+        while let Some(Reverse((_, i))) = queue.pop() {
+            let here = (i as u32 % self.w, i as u32 / self.w);
+            if here == goal {
+                break;
+            }
+            let neighbours = [
+                (here.0.wrapping_sub(1), here.1),
+                (here.0 + 1, here.1),
+                (here.0, here.1.wrapping_sub(1)),
+                (here.0, here.1 + 1),
+            ];
+            for next in neighbours {
+                if !self.is_open(next.0, next.1) || cost[i] + 1 >= cost[idx(next)] {
+                    continue;
+                }
+                cost[idx(next)] = cost[i] + 1;
+                came_from[idx(next)] = i;
+                queue.push(Reverse((cost[i] + 1 + guess(next), idx(next))));
+            }
+        }
+        if cost[idx(goal)] == u32::MAX {
+            return None;
+        }
+        // walk back from the goal, leaving out the cell we start in
+        let mut path = vec![to];
+        let mut i = came_from[idx(goal)];
+        while i != usize::MAX && i != idx(start) {
+            path.push(self.centre(i as u32 % self.w, i as u32 / self.w));
+            i = came_from[i];
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// Walking between patrol points, or standing still without any.
+    Patrol,
+    /// Going after the player, or to where they were last seen.
+    Chase,
+}
+
+pub struct Actor {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    /// Which way it faces, like `Camera::radians`.
+    pub radians: f32,
+    /// Map pixels per second.
+    pub speed: f32,
+    /// How far away it can notice the player.
+    pub sight: f32,
+    /// How wide it looks, in radians.
+    pub fov: f32,
+    pub patrol: Vec<(i32, i32)>,
+    pub state: State,
+    waypoint: usize,
+    // where it's headed and how it's getting there
+    goal: Option<(i32, i32)>,
+    path: Vec<(i32, i32)>,
+}
+
+impl Actor {
+    pub fn new(name: &str, x: f32, y: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            x,
+            y,
+            radians: 0.0,
+            speed: 48.0,
+            sight: 320.0,
+            fov: PI / 2.0,
+            patrol: Vec::new(),
+            state: State::Patrol,
+            waypoint: 0,
+            goal: None,
+            path: Vec::new(),
+        }
+    }
+
+    /// An actor for a map's enemy. It patrols between the markers sharing
+    /// its name, in the order they're listed.
+    pub fn from_thing(thing: &Thing, map: &Map) -> Self {
+        let mut actor = Self::new(&thing.name, thing.x as f32, thing.y as f32);
+        actor.patrol = map
+            .markers
+            .iter()
+            .filter(|m| m.name == thing.name)
+            .map(|m| (m.x, m.y))
+            .collect();
+        actor
+    }
+
+    fn position(&self) -> (i32, i32) {
+        (self.x as i32, self.y as i32)
+    }
+
+    /// Whether `target` is close enough, inside its view and not behind a
+    /// wall.
+    pub fn sees(&self, map: &Map, target: (i32, i32)) -> bool {
+        let (dx, dy) = (target.0 as f32 - self.x, self.y - target.1 as f32);
+        if dx.hypot(dy) > self.sight {
+            return false;
+        }
+        let off = (dy.atan2(dx) - self.radians + PI).rem_euclid(2.0 * PI) - PI;
+        off.abs() <= self.fov / 2.0 && sight::line_of_sight(map, self.position(), target)
+    }
+
+    /// Thinks and moves for `dt` seconds, with the player at `player`.
+    pub fn update(&mut self, map: &Map, nav: &NavGrid, player: (i32, i32), dt: f32) {
+        if self.sees(map, player) {
+            self.state = State::Chase;
+            self.head_for(nav, player);
+        }
+        if self.state == State::Patrol && !self.patrol.is_empty() {
+            let point = self.patrol[self.waypoint % self.patrol.len()];
+            if self.goal != Some(point) {
+                self.head_for(nav, point);
+            }
+        }
+        if self.walk(dt) {
+            self.move_on();
+        }
+    }
+
+    fn move_on(&mut self) {
+        // done with the current goal, reached or not
+        match self.state {
+            // lost them, back to the rounds
+            State::Chase => self.state = State::Patrol,
+            State::Patrol if !self.patrol.is_empty() => {
+                self.waypoint = (self.waypoint + 1) % self.patrol.len();
+            }
+            State::Patrol => {}
+        }
+        self.goal = None;
+        self.path.clear();
+    }
+
+    fn head_for(&mut self, nav: &NavGrid, goal: (i32, i32)) {
+        // only look for a new path when the goal moves to another cell
+        let moved = match self.goal {
+            Some(old) => nav.cell_at(old) != nav.cell_at(goal),
+            None => true,
+        };
+        if moved {
+            let Some(path) = nav.find_path(self.position(), goal) else {
+                // no way there, so try the next thing rather than stand
+                // waiting for a path that won't come
+                self.move_on();
+                return;
+            };
+            self.path = path;
+        } else if let Some(last) = self.path.last_mut() {
+            *last = goal;
+        }
+        self.goal = Some(goal);
+    }
+
+    fn walk(&mut self, dt: f32) -> bool {
+        // returns whether it reached the end of its path
+        let mut step = self.speed * dt;
+        while let Some(&(x, y)) = self.path.first() {
+            let (dx, dy) = (x as f32 - self.x, y as f32 - self.y);
+            let distance = dx.hypot(dy);
+            if distance > 0.0 {
+                self.radians = (-dy).atan2(dx);
+            }
+            if distance > step {
+                self.x += dx / distance * step;
+                self.y += dy / distance * step;
+                return false;
+            }
+            (self.x, self.y) = (x as f32, y as f32);
+            step -= distance;
+            self.path.remove(0);
+            if self.path.is_empty() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Where the camera is, in radians counterclockwise from the way the
+    /// actor faces. This picks which of its sprites shows.
+    pub fn view_angle(&self, cam: &Camera) -> f32 {
+        let (dx, dy) = (cam.x as f32 - self.x, self.y - cam.y as f32);
+        (dy.atan2(dx) - self.radians).rem_euclid(2.0 * PI)
+    }
+
+    pub fn billboard<'a>(&self, cam: &Camera, sprites: &'a Directional) -> Billboard<'a> {
+        Billboard {
+            x: self.x,
+            y: self.y,
+            sprite: sprites.frame(self.view_angle(cam)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Marker, Segment, ThingKind, Wall};
+
+    fn room() -> Map {
+        // 128x128 split by a wall at x 64..72, with a gap below y 112
+        let mut map = Map::new(128, 128);
+        for y in 0..112 {
+            for x in 64..72 {
+                map.map[x + y * 128] = Some(Wall::Stone);
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn paths() {
+        let nav = NavGrid::new(&room(), 16);
+        assert!(!nav.is_open(4, 0) && nav.is_open(4, 7));
+        let path = nav.find_path((24, 24), (104, 24)).unwrap();
+        assert_eq!(path.last(), Some(&(104, 24)));
+        // around through the gap, one cell at a time
+        assert!(path.contains(&(72, 120)));
+        assert!(path.windows(2).all(|w| {
+            let (a, b) = (w[0], w[1]);
+            (a.0 - b.0).abs() + (a.1 - b.1).abs() <= 16
+        }));
+        assert_eq!(nav.find_path((24, 24), (68, 24)), None);
+        assert_eq!(nav.find_path((24, 24), (24, 30)), Some(vec![(24, 30)]));
+
+        // a thin wall across the gap shuts it
+        let mut map = room();
+        map.segments
+            .push(Segment::vertical(112.0, 128.0, 68.0, Wall::Brick));
+        let nav = NavGrid::new(&map, 16);
+        assert_eq!(nav.find_path((24, 24), (104, 24)), None);
+    }
+
+    #[test]
+    fn patrol_and_chase() {
+        let mut map = room();
+        map.add_thing(ThingKind::Enemy, "imp", 24, 24);
+        for (x, y) in [(24, 100), (24, 24)] {
+            map.markers.push(Marker {
+                name: "imp".to_string(),
+                x,
+                y,
+            });
+        }
+        let nav = NavGrid::new(&map, 16);
+        let mut imp = Actor::from_thing(&map.things[0], &map);
+        assert_eq!(imp.patrol, [(24, 100), (24, 24)]);
+
+        // the player is on the far side of the wall, so it just patrols
+        let hidden = (104, 24);
+        for _ in 0..40 {
+            imp.update(&map, &nav, hidden, 0.1);
+            if imp.waypoint == 1 {
+                break;
+            }
+        }
+        assert_eq!(imp.state, State::Patrol);
+        assert_eq!(imp.position(), (24, 100));
+
+        // then comes round the gap into view
+        let player = (100, 124);
+        imp.radians = 0.0;
+        imp.update(&map, &nav, player, 0.1);
+        assert_eq!(imp.state, State::Chase);
+        for _ in 0..100 {
+            imp.update(&map, &nav, player, 0.1);
+        }
+        assert_eq!(imp.position(), player);
+    }
+
+    #[test]
+    fn unreachable_waypoint() {
+        // the second marker is inside the wall, so it skips to the third
+        let mut map = room();
+        map.add_thing(ThingKind::Enemy, "imp", 24, 24);
+        for (x, y) in [(24, 40), (68, 24), (24, 100)] {
+            map.markers.push(Marker {
+                name: "imp".to_string(),
+                x,
+                y,
+            });
+        }
+        let nav = NavGrid::new(&map, 16);
+        let mut imp = Actor::from_thing(&map.things[0], &map);
+        let mut visited = Vec::new();
+        for _ in 0..40 {
+            imp.update(&map, &nav, (104, 24), 0.1);
+            visited.push(imp.position());
+        }
+        assert_eq!(imp.state, State::Patrol);
+        assert!(visited.contains(&(24, 40)) && visited.contains(&(24, 100)));
+    }
+
+    #[test]
+    fn view_angle() {
+        let imp = Actor::new("imp", 100.0, 100.0);
+        let front = Camera {
+            x: 200,
+            y: 100,
+            ..Camera::default()
+        };
+        let left = Camera {
+            x: 100,
+            y: 0,
+            ..Camera::default()
+        };
+        assert_eq!(imp.view_angle(&front), 0.0);
+        assert!((imp.view_angle(&left) - PI / 2.0).abs() < 1e-6);
+        let sprites = Directional::figure(1, 0);
+        assert_eq!(imp.billboard(&left, &sprites).sprite, &sprites.frames[2]);
+    }
+}
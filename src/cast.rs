@@ -1,6 +1,36 @@
-use crate::map::{Map, Wall};
+use crate::map::{Grid, Map, Segment, Wall};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::Deserialize;
 use std::f32::consts::PI;
 
+/// How a camera spreads its rays across the view.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Projection {
+    /// At even angles, like a panorama. Nothing stretches out towards the
+    /// edges, but walls bow at wide fovs.
+    #[default]
+    Angles,
+    /// Evenly across a flat projection plane, set by the camera's direction
+    /// and plane vectors, so straight walls stay straight. At 180 degrees
+    /// and wider there's no such plane and it falls back to `Angles`.
+    Plane,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 2] = [Projection::Angles, Projection::Plane];
+
+    /// How it's written in settings and replays.
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Plane => "plane",
+            Projection::Angles => "angles",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Camera {
     pub x: i32,
     pub y: i32,
@@ -9,10 +39,61 @@ pub struct Camera {
     pub fov: f32,
     pub max_distance: f32,
     pub ray_steps: u32,
+    pub projection: Projection,
 }
 
+/// How tall walls are, in map units. The camera's eye is halfway up.
+pub const WALL_HEIGHT: f32 = 32.0;
+
+/// The narrowest and widest the fov can be zoomed to.
+pub const MIN_FOV: f32 = PI / 36.0;
+pub const MAX_FOV: f32 = 17.0 * PI / 18.0;
+
 impl Camera {
-    pub fn default() -> Self {
+    /// Magnifies the view `factor` times, like a sniper scope, so whatever
+    /// is in the middle looks `factor` times bigger. Below 1.0 zooms out.
+    pub fn zoom(&mut self, factor: f32) {
+        if self.flat() {
+            let half = ((self.fov / 2.0).tan() / factor).atan();
+            self.set_fov(2.0 * half);
+        } else {
+            self.set_fov(self.fov / factor);
+        }
+    }
+
+    /// Kept between `MIN_FOV` and `MAX_FOV`.
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    /// Sees out to `distance`, adding or dropping ray steps so they stay
+    /// the same length.
+    pub fn set_max_distance(&mut self, distance: f32) {
+        let step = self.max_distance / self.ray_steps.max(1) as f32;
+        self.max_distance = distance.clamp(1.0, 16384.0);
+        self.ray_steps = (self.max_distance / step).ceil().max(1.0) as u32;
+    }
+
+    /// Which way the camera faces, as a unit vector with +y up the map.
+    pub fn direction(&self) -> (f32, f32) {
+        (self.radians.cos(), self.radians.sin())
+    }
+
+    /// From the middle of the projection plane, one unit in front of the
+    /// camera, to its left edge.
+    pub fn plane(&self) -> (f32, f32) {
+        let (dx, dy) = self.direction();
+        let half = (self.fov / 2.0).tan();
+        (-dy * half, dx * half)
+    }
+
+    fn flat(&self) -> bool {
+        self.projection == Projection::Plane && self.fov < PI
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
         Self {
             x: 0,
             y: 0,
@@ -21,26 +102,91 @@ impl Camera {
             fov: PI / 3.0,
             max_distance: 512.0,
             ray_steps: 512,
+            projection: Projection::default(),
         }
     }
 }
 
-#[derive(Clone, Copy)]
+/// Which kind of face a ray hit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Side {
+    /// A face running north-south, reached by moving along x.
+    #[default]
+    X,
+    /// A face running east-west, reached by moving along y.
+    Y,
+}
+
+/// Where a ray met a wall: the cell it entered and which face it came in by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit<T> {
+    pub distance: f32,
+    pub x: u32,
+    pub y: u32,
+    pub side: Side,
+    pub wall: T,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Ray<T> {
     pub distance: f32,
     pub angle: f32,
     pub wall: Option<T>, //TODO rename to collision?
+    pub side: Side,
+    /// See-through walls the ray passed before stopping, nearest first.
+    pub through: Vec<Hit<T>>,
+    /// How far the wall is in the camera's projection, see
+    /// `calculate_depth`. This is what sets its height.
+    pub depth: f32,
 }
 
+/// How tall each of a view's rays' walls is, one ray per column, like
+/// `calculate_height`.
 pub fn calculate_heights<T>(rays: &[Ray<T>], cam: &Camera) -> Vec<f32> {
+    let focal = focal_length(cam, rays.len());
     let mut heights = vec![0.0; rays.len()];
     for (i, ray) in rays.iter().enumerate() {
-        let from_axis = 14.0 * cam.max_distance / ray.distance;
-        heights[i] = from_axis;
+        heights[i] = WALL_HEIGHT / 2.0 * focal / ray.depth;
     }
     heights
 }
 
+/// How many pixels one map unit, one unit in front of the camera, covers
+/// in a view `columns` wide.
+pub fn focal_length(cam: &Camera, columns: usize) -> f32 {
+    if cam.flat() {
+        columns as f32 / 2.0 / (cam.fov / 2.0).tan()
+    } else {
+        columns as f32 / cam.fov
+    }
+}
+
+/// `distance` along the ray at `angle`, as the camera's projection sees
+/// it. On a plane that's measured square to the camera's direction, so a
+/// flat wall facing it is the same height all the way across; with angles
+/// it's the distance itself.
+pub fn calculate_depth(distance: f32, angle: f32, cam: &Camera) -> f32 {
+    if cam.flat() {
+        let (dx, dy) = cam.direction();
+        distance * (angle.cos() * dx + angle.sin() * dy)
+    } else {
+        distance
+    }
+}
+
+/// How far above and below the horizon a wall reaches, `distance` along a
+/// ray at `angle` in a view `columns` wide.
+pub fn calculate_height(distance: f32, angle: f32, cam: &Camera, columns: usize) -> f32 {
+    WALL_HEIGHT / 2.0 * focal_length(cam, columns) / calculate_depth(distance, angle, cam)
+}
+
+/// The other way round from `calculate_height`: how far along the ray at
+/// `angle` a wall would be to reach `from_axis` above the horizon.
+pub fn calculate_distance(from_axis: f32, angle: f32, cam: &Camera, columns: usize) -> f32 {
+    let square = WALL_HEIGHT / 2.0 * focal_length(cam, columns) / from_axis;
+    square / calculate_depth(1.0, angle, cam)
+}
+
 pub fn calculate_ray(distance: f32, angle: f32) -> (i32, i32) {
     let x_off = distance * angle.cos();
     let y_off = distance * angle.sin();
@@ -49,55 +195,240 @@ pub fn calculate_ray(distance: f32, angle: f32) -> (i32, i32) {
 
 pub fn calculate_angle(cam: &Camera, span: f32) -> f32 {
     // span: value between 0.0 and 1.0 that's the percent through the field of view
-    let angle = cam.radians + cam.fov * (span - 0.5);
+    let angle = if cam.flat() {
+        // through the point `span` across the projection plane, from its
+        // right edge to its left
+        let ((dx, dy), (px, py)) = (cam.direction(), cam.plane());
+        let along = 2.0 * span - 1.0;
+        (dy + py * along).atan2(dx + px * along)
+    } else {
+        cam.radians + cam.fov * (span - 0.5)
+    };
     const MAX_ANGLE: f32 = 2.0 * PI;
     // angle % MAX_ANGLE but for deranged inconsistent Rust/C math:
     ((angle % MAX_ANGLE) + MAX_ANGLE) % MAX_ANGLE
 }
 
+/// Where across the view, from 0.0 to 1.0 like `calculate_angle`'s span,
+/// something `off` radians counterclockwise from straight ahead shows up.
+/// Infinite for things behind a flat view.
+pub fn calculate_span(cam: &Camera, off: f32) -> f32 {
+    let off = (off + PI).rem_euclid(2.0 * PI) - PI;
+    if !cam.flat() {
+        off / cam.fov + 0.5
+    } else if off.abs() >= PI / 2.0 {
+        f32::INFINITY.copysign(off)
+    } else {
+        0.5 + off.tan() / (2.0 * (cam.fov / 2.0).tan())
+    }
+}
+
 pub fn cast_ray(map: &Map, cam: &Camera, span: f32) -> Ray<Wall> {
-    // step ranges from 0 to 1: percentage throug the fov
+    cast_grid(map, cam, span)
+}
+
+fn is_solid_at<G: Grid>(grid: &G, x: i32, y: i32) -> bool {
+    if x < 0 || y < 0 {
+        return false;
+    }
+    grid.cell(x as u32, y as u32)
+        .is_some_and(|cell| grid.is_solid(&cell))
+}
+
+fn entry_side<G: Grid>(grid: &G, from: (i32, i32), to: (i32, i32)) -> Side {
+    // the last open sample and the first solid one. when the step moved
+    // diagonally, it came in along x if the cell beside `from` on x is solid
+    if from.1 == to.1 || from.0 != to.0 && is_solid_at(grid, to.0, from.1) {
+        Side::X
+    } else {
+        Side::Y
+    }
+}
+
+/// How far along the ray from `origin` at `angle` it crosses `segment`,
+/// if it does. Both are in map space, with +y down.
+pub fn intersect<T>(origin: (f32, f32), angle: f32, segment: &Segment<T>) -> Option<f32> {
+    let cross = |a: (f32, f32), b: (f32, f32)| a.0 * b.1 - a.1 * b.0;
+    let ray = (angle.cos(), -angle.sin());
+    let along = (segment.to.0 - segment.from.0, segment.to.1 - segment.from.1);
+    let denom = cross(ray, along);
+    if denom.abs() < 1e-6 {
+        // running alongside it, which never shows anything
+        return None;
+    }
+    let start = (segment.from.0 - origin.0, segment.from.1 - origin.1);
+    let t = cross(start, along) / denom;
+    let u = cross(start, ray) / denom;
+    (t > 0.0 && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// Every wall a ray runs into, nearest first, out to `max_distance` or
+/// the edge of the grid. A thick wall counts once; going from a see-through
+/// wall straight into a solid one, or back, counts as another. Segments
+/// count each time they're crossed.
+pub struct Hits<'a, G: Grid> {
+    grid: &'a G,
+    origin: (i32, i32),
+    angle: f32,
+    max_distance: f32,
+    steps: u32,
+    step: u32,
+    last: (i32, i32),
+    // what the last sample was in: None for open space, otherwise whether
+    // the wall was see-through
+    inside: Option<bool>,
+    // the segments the ray crosses that are still to come, farthest first
+    segments: Vec<Hit<G::Cell>>,
+}
+
+impl<'a, G: Grid> Hits<'a, G> {
+    /// Marches from `origin` along `angle` in `steps` even steps.
+    pub fn new(grid: &'a G, origin: (i32, i32), angle: f32, max_distance: f32, steps: u32) -> Self {
+        let start = (origin.0 as f32, origin.1 as f32);
+        let mut segments: Vec<_> = grid
+            .segments()
+            .iter()
+            .filter_map(|segment| {
+                let distance = intersect(start, angle, segment)?;
+                let (x, y) = calculate_ray(distance, angle);
+                Some(Hit {
+                    distance,
+                    x: (origin.0 + x).max(0) as u32,
+                    y: (origin.1 - y).max(0) as u32,
+                    side: segment.side(),
+                    wall: segment.wall,
+                })
+            })
+            .filter(|hit| hit.distance < max_distance)
+            .collect();
+        segments.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+        Self {
+            grid,
+            origin,
+            angle,
+            max_distance,
+            steps,
+            step: 0,
+            last: origin,
+            inside: None,
+            segments,
+        }
+    }
+}
+
+impl<G: Grid> Iterator for Hits<'_, G> {
+    type Item = Hit<G::Cell>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (w, h) = self.grid.dimensions();
+        while self.step < self.steps {
+            let dist = self.max_distance * (self.step as f32) / (self.steps as f32);
+            // a segment crossed since the last sample comes first
+            if self.segments.last().is_some_and(|hit| hit.distance <= dist) {
+                return self.segments.pop();
+            }
+            self.step += 1;
+            let (x_off, y_off) = calculate_ray(dist, self.angle);
+            let x = self.origin.0 + x_off;
+            let y = self.origin.1 - y_off; // minus because +y is down
+            if x < 0 || y < 0 || x as u32 >= w || y as u32 >= h {
+                // left the grid, nothing more to hit
+                self.step = self.steps;
+                self.segments.clear();
+                return None;
+            }
+            let cell = self
+                .grid
+                .cell(x as u32, y as u32)
+                .filter(|cell| self.grid.is_solid(cell));
+            let inside = cell.map(|cell| self.grid.is_transparent(&cell));
+            let entered = inside.is_some() && inside != self.inside;
+            let from = self.last;
+            self.inside = inside;
+            self.last = (x, y);
+            if let (true, Some(wall)) = (entered, cell) {
+                return Some(Hit {
+                    distance: dist,
+                    x: x as u32,
+                    y: y as u32,
+                    side: entry_side(self.grid, from, (x, y)),
+                    wall,
+                });
+            }
+        }
+        // past the last sample but still in reach
+        self.segments.pop()
+    }
+}
+
+/// All the walls along the ray at `span` through the camera's view, see
+/// `Hits`.
+pub fn cast_hits<'a, G: Grid>(grid: &'a G, cam: &Camera, span: f32) -> Hits<'a, G> {
     let angle = calculate_angle(cam, span);
-    for step in 0..cam.ray_steps {
-        let dist = cam.max_distance * (step as f32) / (cam.ray_steps as f32);
-        let offset = calculate_ray(dist, angle);
-        let x_off = offset.0;
-        let y_off = offset.1;
-        let x = (cam.x + x_off) as u32;
-        let y = (cam.y - y_off) as u32; // minus because +y is down
-        let idx = (x + y * map.w) as usize;
-        // TODO: make this pattern matching
-        if map.map[idx].is_some() {
-            return Ray {
-                distance: dist,
-                wall: map.map[idx],
-                angle: angle,
-            };
+    Hits::new(grid, (cam.x, cam.y), angle, cam.max_distance, cam.ray_steps)
+}
+
+pub fn cast_grid<G: Grid>(grid: &G, cam: &Camera, span: f32) -> Ray<G::Cell> {
+    // span ranges from 0 to 1: percentage throug the fov
+    let angle = calculate_angle(cam, span);
+    let mut through = Vec::new();
+    for hit in cast_hits(grid, cam, span) {
+        if grid.is_transparent(&hit.wall) {
+            through.push(hit);
+            continue;
         }
+        return Ray {
+            distance: hit.distance,
+            wall: Some(hit.wall),
+            angle,
+            side: hit.side,
+            through,
+            depth: calculate_depth(hit.distance, angle, cam),
+        };
     }
-    return Ray {
+    Ray {
         distance: cam.max_distance,
         wall: None,
-        angle: angle,
-    };
+        angle,
+        side: Side::default(),
+        through,
+        depth: calculate_depth(cam.max_distance, angle, cam),
+    }
 }
 
-pub fn cast_fov(map: &Map, cam: &Camera) -> Vec<Ray<Wall>> {
-    let mut view = Vec::with_capacity(map.w as usize);
-    view.resize(
-        map.w as usize,
-        Ray {
-            distance: cam.max_distance,
-            wall: None,
-            angle: 0.0,
-        },
-    );
-    for i in 0..512 {
-        let step = (i as f32) / cam.max_distance;
-        let ray = cast_ray(&map, &cam, step);
-        view[i as usize] = ray;
+pub fn cast_fov<G>(grid: &G, cam: &Camera) -> Vec<Ray<G::Cell>>
+where
+    G: Grid + Sync,
+    G::Cell: Send,
+{
+    cast_columns(grid, cam, 512)
+}
+
+fn cast_column<G: Grid>(grid: &G, cam: &Camera, column: u32, columns: u32) -> Ray<G::Cell> {
+    let span = (column as f32) / (columns as f32);
+    cast_grid(grid, cam, span)
+}
+
+pub fn cast_columns<G>(grid: &G, cam: &Camera, columns: u32) -> Vec<Ray<G::Cell>>
+where
+    G: Grid + Sync,
+    G::Cell: Send,
+{
+    // one ray per screen column. columns don't depend on each other, so
+    // with the `parallel` feature they get spread across threads
+    #[cfg(feature = "parallel")]
+    {
+        (0..columns)
+            .into_par_iter()
+            .map(|i| cast_column(grid, cam, i, columns))
+            .collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (0..columns)
+            .map(|i| cast_column(grid, cam, i, columns))
+            .collect()
     }
-    view
 }
 
 #[cfg(test)]
@@ -159,4 +490,379 @@ mod tests {
         let result = calculate_angle(&cam, 1.0);
         assert_approx_eq!(result, 1.0 * PI / 3.0);
     }
+
+    #[test]
+    fn columns_span_fov() {
+        let map = crate::map::spooky_map();
+        let cam = Camera {
+            x: 240,
+            y: 464,
+            ..Camera::default()
+        };
+        let view = cast_columns(&map, &cam, 1920);
+        assert_eq!(view.len(), 1920);
+        assert_approx_eq!(view[0].angle, calculate_angle(&cam, 0.0));
+        assert_approx_eq!(view[960].angle, calculate_angle(&cam, 0.5));
+    }
+
+    // rays at a flat wall x = `wall` ahead of a camera facing +x, at the
+    // exact distances rather than marched ones
+    fn wall_rays(cam: &Camera, wall: f32, columns: usize) -> Vec<Ray<()>> {
+        (0..columns)
+            .map(|i| {
+                let angle = calculate_angle(cam, i as f32 / columns as f32);
+                let distance = wall / angle.cos();
+                Ray {
+                    distance,
+                    angle,
+                    wall: Some(()),
+                    side: Side::X,
+                    through: Vec::new(),
+                    depth: calculate_depth(distance, angle, cam),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn straight_walls() {
+        for degrees in [20.0f32, 60.0, 90.0, 150.0] {
+            let cam = Camera {
+                fov: degrees.to_radians(),
+                projection: Projection::Plane,
+                ..Camera::default()
+            };
+            // facing it square, the wall is the same height all the way across
+            let heights = calculate_heights(&wall_rays(&cam, 100.0, 64), &cam);
+            for height in &heights {
+                assert_approx_eq!(*height / heights[32], 1.0, 1e-4);
+            }
+            // at a slant its top edge is still a straight line, so the
+            // height goes up by the same amount every column
+            let cam = Camera {
+                radians: 0.2,
+                ..cam
+            };
+            let heights = calculate_heights(&wall_rays(&cam, 100.0, 64), &cam);
+            let slope = heights[1] - heights[0];
+            for pair in heights.windows(2) {
+                assert_approx_eq!(pair[1] - pair[0], slope, slope.abs() / 100.0);
+            }
+        }
+    }
+
+    #[test]
+    fn straight_walls_on_a_map() {
+        // the same, cast at a real wall: facing it square from 95 away,
+        // every column sees it the same height whatever the fov, give or
+        // take the last step of the ray going a pixel into it
+        let mut map = Map::new(200, 1000);
+        for y in 0..1000 {
+            map.map[100 + y * 200] = Some(Wall::Stone);
+        }
+        for degrees in [30.0f32, 60.0, 90.0, 120.0, 150.0] {
+            let cam = Camera {
+                x: 5,
+                y: 500,
+                radians: 0.0,
+                fov: degrees.to_radians(),
+                projection: Projection::Plane,
+                ..Camera::default()
+            };
+            let rays = cast_columns(&map, &cam, 64);
+            assert!(rays.iter().all(|ray| ray.wall == Some(Wall::Stone)));
+            let square = calculate_height(95.0, 0.0, &cam, 64);
+            for height in calculate_heights(&rays, &cam) {
+                assert!((square * 95.0 / 96.0..=square).contains(&height));
+            }
+        }
+    }
+
+    #[test]
+    fn projections() {
+        let plane = Camera {
+            radians: 1.0,
+            fov: 100f32.to_radians(),
+            projection: Projection::Plane,
+            ..Camera::default()
+        };
+        let angles = Camera {
+            projection: Projection::Angles,
+            ..plane
+        };
+        // both start and end at the edges of the fov
+        for cam in [&plane, &angles] {
+            assert_approx_eq!(calculate_angle(cam, 0.0), 1.0 - 50f32.to_radians());
+            assert_approx_eq!(calculate_angle(cam, 1.0), 1.0 + 50f32.to_radians());
+        }
+        // a quarter of the way across: a quarter of the fov on angles, and
+        // half way to the edge of the plane on a plane
+        assert_approx_eq!(calculate_angle(&angles, 0.25), 1.0 - 25f32.to_radians());
+        let ((dx, dy), (px, py)) = (plane.direction(), plane.plane());
+        let towards = (dy - py / 2.0).atan2(dx - px / 2.0);
+        assert_approx_eq!(calculate_angle(&plane, 0.25), towards);
+
+        // a flat wall bows out on angles, nearest in the middle
+        let square = Camera {
+            radians: 0.0,
+            ..angles
+        };
+        let rays = wall_rays(&square, 100.0, 64);
+        assert_approx_eq!(rays[10].depth, rays[10].distance);
+        let heights = calculate_heights(&rays, &square);
+        assert!(heights[32] > heights[16] && heights[16] > heights[0]);
+    }
+
+    #[test]
+    fn zoom_and_distance() {
+        let mut cam = Camera::default();
+        let before = calculate_height(100.0, cam.radians, &cam, 512);
+        cam.zoom(2.0);
+        assert_approx_eq!(
+            calculate_height(100.0, cam.radians, &cam, 512),
+            2.0 * before,
+            1e-3
+        );
+        cam.zoom(0.5);
+        assert_approx_eq!(cam.fov, PI / 3.0);
+        cam.zoom(1000.0);
+        assert_eq!(cam.fov, MIN_FOV);
+
+        // seeing further doesn't change how big things are, and keeps the
+        // steps the same length
+        let mut cam = Camera::default();
+        cam.set_max_distance(1024.0);
+        assert_eq!((cam.max_distance, cam.ray_steps), (1024.0, 1024));
+        assert_approx_eq!(calculate_height(100.0, 0.0, &cam, 512), before, 1e-3);
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Tile {
+        Floor,
+        Door { locked: bool },
+    }
+
+    struct Level {
+        tiles: Vec<Tile>,
+        w: u32,
+    }
+
+    impl Grid for Level {
+        type Cell = Tile;
+
+        fn dimensions(&self) -> (u32, u32) {
+            (self.w, self.tiles.len() as u32 / self.w)
+        }
+
+        fn cell(&self, x: u32, y: u32) -> Option<Tile> {
+            self.tiles.get((x + y * self.w) as usize).copied()
+        }
+
+        fn is_solid(&self, cell: &Tile) -> bool {
+            *cell != Tile::Floor
+        }
+    }
+
+    #[test]
+    fn custom_grid() {
+        // a 64x64 room of floor with a locked door along x = 40
+        let mut tiles = vec![Tile::Floor; 64 * 64];
+        for y in 0..64 {
+            tiles[40 + y * 64] = Tile::Door { locked: true };
+        }
+        let level = Level { tiles, w: 64 };
+        let cam = Camera {
+            x: 8,
+            y: 32,
+            radians: 0.0,
+            max_distance: 64.0,
+            ray_steps: 64,
+            ..Camera::default()
+        };
+        let ray = cast_grid(&level, &cam, 0.5);
+        assert_eq!(ray.wall, Some(Tile::Door { locked: true }));
+        assert_approx_eq!(ray.distance, 32.0);
+        assert_eq!(ray.side, Side::X);
+    }
+
+    #[test]
+    fn see_through() {
+        // spooky_map's crystal block sits at x 384..416, y 192..224, with a
+        // stone wall behind it at y 64..96
+        let map = crate::map::spooky_map();
+        let cam = Camera {
+            x: 400,
+            y: 300,
+            radians: 0.5 * PI,
+            ..Camera::default()
+        };
+        let ray = cast_ray(&map, &cam, 0.5);
+        assert_eq!(ray.wall, Some(Wall::Stone));
+        assert_eq!(ray.through.len(), 1);
+        let hit = ray.through[0];
+        assert_eq!(
+            (hit.wall, hit.side, hit.x, hit.y),
+            (Wall::Crystal, Side::Y, 400, 223)
+        );
+        assert!(hit.distance < ray.distance);
+    }
+
+    #[test]
+    fn every_hit() {
+        // a crystal pane, then two stone walls, the second touching the grid edge
+        let mut map = Map::new(100, 10);
+        for y in 0..10 {
+            map.map[20 + y * 100] = Some(Wall::Crystal);
+            map.map[21 + y * 100] = Some(Wall::Stone);
+            for x in 40..50 {
+                map.map[x + y * 100] = Some(Wall::Stone);
+            }
+            map.map[99 + y * 100] = Some(Wall::Dirt);
+        }
+        let cam = Camera {
+            x: 5,
+            y: 5,
+            max_distance: 200.0,
+            ray_steps: 200,
+            ..Camera::default()
+        };
+        let hits: Vec<_> = cast_hits(&map, &cam, 0.5)
+            .map(|hit| (hit.distance, hit.x, hit.wall, hit.side))
+            .collect();
+        assert_eq!(
+            hits,
+            [
+                (15.0, 20, Wall::Crystal, Side::X),
+                (16.0, 21, Wall::Stone, Side::X),
+                (35.0, 40, Wall::Stone, Side::X),
+                (94.0, 99, Wall::Dirt, Side::X),
+            ]
+        );
+        // cutting it short keeps only what's in reach
+        let cam = Camera {
+            max_distance: 40.0,
+            ray_steps: 40,
+            ..cam
+        };
+        assert_eq!(cast_hits(&map, &cam, 0.5).count(), 3);
+        assert_eq!(cast_ray(&map, &cam, 0.5).distance, 16.0);
+    }
+
+    #[test]
+    fn segments() {
+        // straight at a diagonal from (10, 0) to (30, 20): it crosses y = 10
+        // at x = 20
+        let diagonal = Segment::new((10.0, 0.0), (30.0, 20.0), Wall::Brick);
+        assert_approx_eq!(intersect((0.0, 10.0), 0.0, &diagonal).unwrap(), 20.0);
+        assert_eq!(intersect((0.0, 10.0), PI, &diagonal), None);
+        assert_eq!(intersect((0.0, 30.0), 0.0, &diagonal), None);
+
+        // a thin crystal pane on a grid line, a brick diagonal, then the
+        // stone wall behind them both
+        let mut map = Map::new(100, 20);
+        for y in 0..20 {
+            map.map[60 + y * 100] = Some(Wall::Stone);
+        }
+        map.segments
+            .push(Segment::vertical(0.0, 20.0, 15.0, Wall::Crystal));
+        map.segments
+            .push(Segment::new((30.0, 5.0), (40.0, 15.0), Wall::Brick));
+        let cam = Camera {
+            x: 5,
+            y: 10,
+            max_distance: 100.0,
+            ray_steps: 100,
+            ..Camera::default()
+        };
+        let hits: Vec<_> = cast_hits(&map, &cam, 0.5)
+            .map(|hit| (hit.distance, hit.x, hit.wall, hit.side))
+            .collect();
+        assert_eq!(
+            hits,
+            [
+                (10.0, 15, Wall::Crystal, Side::X),
+                (30.0, 35, Wall::Brick, Side::Y),
+                (55.0, 60, Wall::Stone, Side::X),
+            ]
+        );
+        let ray = cast_ray(&map, &cam, 0.5);
+        assert_eq!((ray.wall, ray.distance), (Some(Wall::Brick), 30.0));
+        assert_eq!(ray.through.len(), 1);
+
+        // and they round trip through map files
+        let loaded: Map = map.to_string().parse().unwrap();
+        assert_eq!(loaded.segments, map.segments);
+    }
+
+    #[test]
+    fn sides() {
+        let map = crate::map::spooky_map();
+        // straight up the corridor into the horizontal wall at y = 64..96
+        let cam = Camera {
+            x: 240,
+            y: 300,
+            radians: 0.5 * PI,
+            ..Camera::default()
+        };
+        assert_eq!(cast_ray(&map, &cam, 0.5).side, Side::Y);
+        // across into the outer wall on the right
+        let cam = Camera {
+            x: 400,
+            y: 464,
+            radians: 0.0,
+            ..Camera::default()
+        };
+        assert_eq!(cast_ray(&map, &cam, 0.5).side, Side::X);
+    }
+
+    #[test]
+    fn ray_leaving_grid() {
+        let level = Level {
+            tiles: vec![Tile::Floor; 16 * 16],
+            w: 16,
+        };
+        let cam = Camera {
+            x: 8,
+            y: 8,
+            ..Camera::default()
+        };
+        let ray = cast_grid(&level, &cam, 0.5);
+        assert_eq!(ray.wall, None);
+        assert_approx_eq!(ray.distance, cam.max_distance);
+    }
+
+    #[test]
+    fn golden_view() {
+        // fixed results for the hall, which threaded casting has to match
+        // as well as serial
+        let map = crate::map::spooky_map();
+        let cam = Camera {
+            x: 240,
+            y: 464,
+            radians: 0.5 * PI,
+            ..Camera::default()
+        };
+        let view: Vec<_> = cast_columns(&map, &cam, 12)
+            .into_iter()
+            .map(|ray| (ray.distance, ray.wall, ray.side))
+            .collect();
+        let (stone, x, y) = (Some(Wall::Stone), Side::X, Side::Y);
+        assert_eq!(
+            view,
+            [
+                (57.0, stone, y),
+                (55.0, stone, y),
+                (53.0, stone, y),
+                (62.0, stone, x),
+                (115.0, stone, y),
+                (114.0, stone, y),
+                (113.0, stone, y),
+                (114.0, stone, y),
+                (115.0, stone, y),
+                (66.0, stone, x),
+                (53.0, stone, y),
+                (55.0, stone, y),
+            ]
+        );
+    }
 }
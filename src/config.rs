@@ -0,0 +1,340 @@
+// Engine settings from a TOML file, with command line flags on top. Every
+// setting has a default, so a file only needs the ones it changes:
+//
+//     [view]
+//     width = 320
+//     height = 200
+//     fov_degrees = 75
+//     projection = "plane"
+//
+//     [start]
+//     map = "levels/crypt.map"
+//     spawn = { x = 100, y = 200, degrees = 90 }
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::cast::{Camera, Projection};
+use crate::error::{self, Error, ParseError, Result};
+use crate::map::Map;
+
+/// The 3D view and how rays are cast for it.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct View {
+    pub width: u32,
+    pub height: u32,
+    pub fov_degrees: f32,
+    pub max_distance: f32,
+    pub ray_steps: u32,
+    /// "plane" for rays spread evenly across a flat screen, or "angles" for
+    /// rays at even angles.
+    pub projection: Projection,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        let cam = Camera::default();
+        Self {
+            width: 512,
+            height: 512,
+            fov_degrees: cam.fov.to_degrees().round(),
+            max_distance: cam.max_distance,
+            ray_steps: cam.ray_steps,
+            projection: cam.projection,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Window {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        // the view with the map beside it
+        Self {
+            width: 1028,
+            height: 512,
+        }
+    }
+}
+
+/// Where the camera starts, instead of the map's own spawn.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spawn {
+    pub x: i32,
+    pub y: i32,
+    /// Which way it faces, counterclockwise from +x.
+    #[serde(default)]
+    pub degrees: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Start {
+    /// A built-in map's name or a map file's path.
+    pub map: String,
+    pub spawn: Option<Spawn>,
+}
+
+impl Default for Start {
+    fn default() -> Self {
+        Self {
+            map: "spooky".to_string(),
+            spawn: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub view: View,
+    pub window: Window,
+    pub start: Start,
+}
+
+/// The command line flags that override settings, each taking a value.
+pub const FLAGS: &str = "--width N --height N --fov DEGREES --max-distance D --ray-steps N \
+     --projection plane|angles --window WxH --map MAP --spawn X,Y[,DEGREES]";
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        error::load(path.as_ref())
+    }
+
+    /// The first setting that's out of range, if any.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(Error::Config(message));
+        let view = &self.view;
+        if !(1..=4096).contains(&view.width) || !(1..=4096).contains(&view.height) {
+            return invalid(format!(
+                "view size {}x{} has to be from 1x1 to 4096x4096",
+                view.width, view.height
+            ));
+        }
+        if !(view.fov_degrees > 0.0 && view.fov_degrees < 180.0) {
+            return invalid(format!(
+                "fov_degrees {} has to be between 0 and 180",
+                view.fov_degrees
+            ));
+        }
+        if !(view.max_distance > 0.0 && view.max_distance.is_finite()) {
+            return invalid(format!(
+                "max_distance {} has to be more than 0",
+                view.max_distance
+            ));
+        }
+        if view.ray_steps == 0 {
+            return invalid("ray_steps has to be at least 1".to_string());
+        }
+        if self.window.width == 0 || self.window.height == 0 {
+            return invalid(format!(
+                "window size {}x{} can't be empty",
+                self.window.width, self.window.height
+            ));
+        }
+        Ok(())
+    }
+
+    /// Applies flags like `--fov 90` from `args`, giving back the
+    /// arguments that aren't flags. Doesn't validate the result.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<Vec<String>> {
+        fn number<T: FromStr>(flag: &str, value: &str) -> Result<T> {
+            value
+                .parse()
+                .map_err(|_| Error::Config(format!("{flag}: {value:?} isn't a number")))
+        }
+        let mut rest = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let flag = arg.as_str();
+            if !flag.starts_with("--") {
+                rest.push(arg.clone());
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| Error::Config(format!("{flag} needs a value")))?
+                .as_str();
+            match flag {
+                "--width" => self.view.width = number(flag, value)?,
+                "--height" => self.view.height = number(flag, value)?,
+                "--fov" => self.view.fov_degrees = number(flag, value)?,
+                "--max-distance" => self.view.max_distance = number(flag, value)?,
+                "--ray-steps" => self.view.ray_steps = number(flag, value)?,
+                "--projection" => {
+                    self.view.projection = Projection::ALL
+                        .into_iter()
+                        .find(|p| p.name() == value)
+                        .ok_or_else(|| {
+                            Error::Config(format!("{flag}: {value:?} isn't plane or angles"))
+                        })?;
+                }
+                "--window" => {
+                    let (w, h) = value
+                        .split_once('x')
+                        .ok_or_else(|| Error::Config(format!("{flag}: {value:?} isn't WxH")))?;
+                    self.window.width = number(flag, w)?;
+                    self.window.height = number(flag, h)?;
+                }
+                "--map" => self.start.map = value.to_string(),
+                "--spawn" => {
+                    let parts: Vec<&str> = value.split(',').collect();
+                    let (x, y, degrees) = match parts[..] {
+                        [x, y] => (x, y, "0"),
+                        [x, y, degrees] => (x, y, degrees),
+                        _ => {
+                            return Err(Error::Config(format!(
+                                "{flag}: {value:?} isn't X,Y or X,Y,DEGREES"
+                            )))
+                        }
+                    };
+                    self.start.spawn = Some(Spawn {
+                        x: number(flag, x)?,
+                        y: number(flag, y)?,
+                        degrees: number(flag, degrees)?,
+                    });
+                }
+                _ => {
+                    return Err(Error::Config(format!(
+                        "unknown flag {flag}, the flags are {FLAGS}"
+                    )))
+                }
+            }
+        }
+        Ok(rest)
+    }
+
+    /// A camera with these view settings, at the configured spawn or else
+    /// the map's. Only a configured spawn has to be somewhere the camera
+    /// can stand; a bad spawn in the map is the map's problem, and
+    /// validating it warns about that instead.
+    pub fn camera(&self, map: &Map) -> Result<Camera> {
+        let (x, y, radians) = match self.start.spawn {
+            Some(spawn) => {
+                let (x, y) = (spawn.x, spawn.y);
+                if x < 0 || y < 0 || x as u32 >= map.w || y as u32 >= map.h {
+                    return Err(Error::Config(format!(
+                        "spawn ({x}, {y}) is outside the {}x{} map",
+                        map.w, map.h
+                    )));
+                }
+                if map.map[(x as u32 + y as u32 * map.w) as usize].is_some() {
+                    return Err(Error::Config(format!("spawn ({x}, {y}) is inside a wall")));
+                }
+                (x, y, spawn.degrees.to_radians())
+            }
+            None => (map.spawn.x, map.spawn.y, map.spawn.radians),
+        };
+        Ok(Camera {
+            x,
+            y,
+            radians,
+            fov: self.view.fov_degrees.to_radians(),
+            max_distance: self.view.max_distance,
+            ray_steps: self.view.ray_steps,
+            projection: self.view.projection,
+            ..Camera::default()
+        })
+    }
+}
+
+impl FromStr for Config {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let config: Config = toml::from_str(s).map_err(|e| ParseError {
+            line: e
+                .span()
+                .map_or(0, |span| s[..span.start].matches('\n').count() + 1),
+            message: e.message().to_string(),
+        })?;
+        config.validate().map_err(|e| ParseError {
+            line: 0,
+            message: e.to_string(),
+        })?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map;
+
+    #[test]
+    fn parse() {
+        let config: Config = "[view]\nwidth = 320\nfov_degrees = 90\nprojection = \"plane\"\n\n\
+                              [start]\nspawn = { x = 100, y = 96, degrees = 180 }\n"
+            .parse()
+            .unwrap();
+        assert_eq!((config.view.width, config.view.height), (320, 512));
+        assert_eq!(config.window, Window::default());
+        let cam = config.camera(&map::spooky_map()).unwrap();
+        assert_eq!((cam.x, cam.y), (100, 96));
+        assert!((cam.radians - std::f32::consts::PI).abs() < 1e-6);
+        assert!((cam.fov - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(cam.projection, Projection::Plane);
+        assert_eq!(Config::default().view.fov_degrees, 60.0);
+
+        let err = |s: &str| s.parse::<Config>().err().map(|e| e.to_string());
+        assert_eq!(
+            err("[view]\nfov_degrees = 200"),
+            Some("fov_degrees 200 has to be between 0 and 180".to_string())
+        );
+        assert!(err("[view]\n\nwidht = 3").is_some_and(|e| e.starts_with("line 3: unknown field")));
+        assert!(err("[view]\nwidth = \"big\"").is_some_and(|e| e.starts_with("line 2:")));
+        assert!(err("[view]\nprojection = \"fisheye\"").is_some_and(|e| e.starts_with("line 2:")));
+    }
+
+    #[test]
+    fn flags() {
+        let mut config = Config::default();
+        let args: Vec<String> = ["term", "--fov", "90", "crypt.map", "--window", "800x600"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(config.apply_args(&args).unwrap(), ["term", "crypt.map"]);
+        assert_eq!(config.view.fov_degrees, 90.0);
+        assert_eq!((config.window.width, config.window.height), (800, 600));
+
+        let apply = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            Config::default()
+                .apply_args(&args)
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert_eq!(apply(&["--fov"]), Some("--fov needs a value".to_string()));
+        assert_eq!(
+            apply(&["--ray-steps", "lots"]),
+            Some("--ray-steps: \"lots\" isn't a number".to_string())
+        );
+        assert!(apply(&["--zoom", "2"]).is_some_and(|e| e.starts_with("unknown flag --zoom")));
+
+        // a map with its spawn in a wall still starts, only the spawn
+        // asked for has to be somewhere the camera can stand
+        let mut walled = map::spooky_map();
+        (walled.spawn.x, walled.spawn.y) = (0, 0);
+        assert_eq!(
+            config.camera(&walled).map(|c| (c.x, c.y)).ok(),
+            Some((0, 0))
+        );
+        config
+            .apply_args(&["--spawn".to_string(), "0,0".to_string()])
+            .unwrap();
+        assert_eq!(
+            config
+                .camera(&map::spooky_map())
+                .err()
+                .map(|e| e.to_string()),
+            Some("spawn (0, 0) is inside a wall".to_string())
+        );
+    }
+}
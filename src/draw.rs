@@ -1,112 +1,635 @@
-use crate::cast::{self, Camera, Ray};
-use crate::map::{Map, Wall};
+use crate::cast::{self, Camera, Ray, Side};
+use crate::frame::Frame;
+use crate::light::Lightmap;
+use crate::map::{Map, SeeThrough, Wall};
+use crate::palette::{IndexedImage, RUST_GOLD_8 as PALETTE};
 use image::Rgb;
-use std::cmp;
-
-const PALETTE: [Rgb<u8>; 8] = [
-    // Rust Gold 8 Palette
-    // https://lospec.com/palette-list/rust-gold-8
-    Rgb([246, 205, 38]), // Gold
-    Rgb([172, 107, 38]), // Orange
-    Rgb([86, 50, 38]),   // Rust
-    Rgb([51, 28, 23]),   // Maroon
-    Rgb([187, 127, 87]), // Creamsicle
-    Rgb([114, 89, 86]),  // Purple
-    Rgb([57, 57, 57]),   // Gray
-    Rgb([32, 32, 32]),   // Black
-];
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
-fn pick_color(wall: Option<Wall>) -> Rgb<u8> {
+fn pick_ramp(wall: Option<Wall>) -> &'static [u8] {
+    // palette indices from a material's own color down to black, for
+    // shading. the first entry is the unshaded color
     match wall {
-        Some(Wall::Dirt) => PALETTE[2],
-        Some(Wall::Brick) => PALETTE[0],
-        Some(Wall::Stone) => PALETTE[6],
-        Some(Wall::Crystal) => PALETTE[4],
-        None => PALETTE[7],
+        Some(Wall::Dirt) => &[2, 3, 7],
+        Some(Wall::Brick) => &[0, 1, 2, 3, 7],
+        Some(Wall::Stone) => &[6, 7],
+        Some(Wall::Crystal) => &[4, 5, 3, 7],
+        Some(Wall::Grate) => &[1, 2, 3, 7],
+        None => &[7],
     }
 }
 
-const HORIZONTAL_COLORS: [Rgb<u8>; 2] = [
-    PALETTE[7], // Ceiling
-    PALETTE[3], // Floor
-];
+fn pick_index(wall: Option<Wall>) -> u8 {
+    pick_ramp(wall)[0]
+}
+
+fn pick_color(wall: Option<Wall>) -> Rgb<u8> {
+    PALETTE[pick_index(wall) as usize]
+}
 
-pub fn draw_map(img: &mut image::RgbImage, map: &Map) {
+fn tinted_index(idx: u8, tint: [f32; 3]) -> u8 {
+    // the palette entry nearest to `idx`'s color under the light, so lit
+    // palette indices still take on the light's color
+    if tint == [1.0; 3] {
+        return idx;
+    }
+    let color = PALETTE[idx as usize].0;
+    let lit = [0, 1, 2].map(|i| color[i] as f32 * tint[i]);
+    let far = |c: &Rgb<u8>| -> f32 { (0..3).map(|i| (c.0[i] as f32 - lit[i]).powi(2)).sum() };
+    (0..PALETTE.len())
+        .min_by(|&a, &b| far(&PALETTE[a]).total_cmp(&far(&PALETTE[b])))
+        .unwrap_or(idx as usize) as u8
+}
+
+const CEILING_RAMP: &[u8] = &[7];
+const FLOOR_RAMP: &[u8] = &[3, 7];
+
+pub fn draw_map(img: &mut impl Frame, map: &Map) {
     for y in 0..map.h {
         for x in 0..map.w {
             let idx = (x + y * map.w) as usize;
             let wall = map.map[idx];
             let color = pick_color(wall);
 
-            img.put_pixel(x, y, color);
+            img.put(x, y, color);
+        }
+    }
+    // thin walls on top, a pixel at a time along each
+    for segment in &map.segments {
+        let color = pick_color(Some(segment.wall));
+        let steps = segment.length().ceil().max(1.0) as u32;
+        for i in 0..=steps {
+            let t = i as f32 / steps as f32;
+            let x = segment.from.0 + (segment.to.0 - segment.from.0) * t;
+            let y = segment.from.1 + (segment.to.1 - segment.from.1) * t;
+            if x >= 0.0 && y >= 0.0 && (x as u32) < map.w && (y as u32) < map.h {
+                img.put(x as u32, y as u32, color);
+            }
         }
     }
 }
 
-pub fn draw_view(img: &mut image::RgbImage, view: &[Ray<Wall>], cam: &Camera) {
-    let heights = cast::calculate_heights(view, cam);
-    for (i, ray) in view.iter().enumerate() {
-        let color = pick_color(ray.wall);
-        let mut from_axis = heights[i] as u32;
-        from_axis = cmp::min(from_axis, cam.max_distance as u32);
-        let x = 511 - i as u32;
-        for y in 0..from_axis as u32 {
-            // make sure there's no out of bounds errors
-            if let Some(p) = img.get_pixel_mut_checked(x, 256 + y) {
-                *p = color;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Round to the nearest ramp entry, which bands.
+    #[default]
+    Off,
+    /// 4x4 ordered Bayer dithering between neighbouring ramp entries.
+    Bayer,
+}
+
+/// Darkens the view down each material's ramp of palette entries. The
+/// default shades nothing, which draws flat colors.
+#[derive(Clone, Copy, Default)]
+pub struct Shading<'a> {
+    /// How far down its ramp something at `max_distance` gets, from 0.0
+    /// for no fog to 1.0 for fully black.
+    pub fog: f32,
+    /// Extra shade on east-west faces so corners stand out.
+    pub side: f32,
+    pub dither: Dither,
+    /// Dim light shades further down the ramp, and colored light tints:
+    /// exactly in an RGB image, or to the nearest palette entry when
+    /// drawing palette indices.
+    pub lights: Option<&'a Lightmap>,
+}
+
+const BAYER_4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl Shading<'_> {
+    fn threshold(&self, x: u32, y: u32) -> f32 {
+        match self.dither {
+            Dither::Off => 0.5,
+            Dither::Bayer => (BAYER_4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0,
+        }
+    }
+
+    fn pick(&self, ramp: &[u8], shade: f32, x: u32, y: u32) -> u8 {
+        // shade 0.0 is the top of the ramp and 1.0 the bottom
+        let pos = shade.clamp(0.0, 1.0) * (ramp.len() - 1) as f32;
+        let lo = pos as usize;
+        if lo + 1 < ramp.len() && pos - lo as f32 > self.threshold(x, y) {
+            ramp[lo + 1]
+        } else {
+            ramp[lo]
+        }
+    }
+
+    fn light(&self, cam: &Camera, angle: f32, distance: f32) -> (f32, [f32; 3]) {
+        // extra shade and color tint for the point `distance` along a ray
+        let Some(lights) = self.lights else {
+            return (0.0, [1.0; 3]);
+        };
+        let (x_off, y_off) = (distance * angle.cos(), distance * angle.sin());
+        let rgb = lights.at(cam.x as f32 + x_off, cam.y as f32 - y_off);
+        // brightness shades down the ramp and the hue is left to the tint,
+        // so a strong red light reads as bright red rather than dim
+        let brightest = rgb[0].max(rgb[1]).max(rgb[2]);
+        let tint = if brightest > 0.0 {
+            rgb.map(|c| c / brightest)
+        } else {
+            [1.0; 3]
+        };
+        (1.0 - brightest.min(1.0), tint)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Face {
+    ramp: &'static [u8],
+    shade: f32,
+    tint: [f32; 3],
+    from_axis: u32,
+}
+
+impl Face {
+    fn new(
+        wall: Option<Wall>,
+        side: Side,
+        angle: f32,
+        distance: f32,
+        (cam, columns): (&Camera, usize),
+        shading: &Shading,
+    ) -> Self {
+        let height = cast::calculate_height(distance, angle, cam, columns);
+        let (dark, tint) = shading.light(cam, angle, distance);
+        let mut shade = shading.fog * distance / cam.max_distance + dark;
+        if side == Side::Y {
+            shade += shading.side;
+        }
+        Face {
+            ramp: pick_ramp(wall),
+            shade,
+            tint,
+            // anything taller covers the whole view anyway
+            from_axis: height.min(65536.0) as u32,
+        }
+    }
+
+    fn covers(&self, y: u32, horizon: u32) -> bool {
+        horizon.saturating_sub(self.from_axis) < y && y < horizon + self.from_axis
+    }
+}
+
+/// A see-through wall in front of a column's solid one.
+#[derive(Clone, Copy)]
+struct Layer {
+    face: Face,
+    see_through: SeeThrough,
+    // where along the wall the ray went in, for the grate's bars
+    u: u32,
+}
+
+impl Layer {
+    fn shows(&self, x: u32, y: u32, horizon: u32) -> bool {
+        match self.see_through {
+            SeeThrough::Opaque => true,
+            // a screen door rather than a blend, so it works the same when
+            // drawing palette indices
+            SeeThrough::Alpha(alpha) => {
+                (BAYER_4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0 < alpha
             }
-            if let Some(p) = img.get_pixel_mut_checked(x, 256_u32.saturating_sub(y)) {
-                *p = color;
+            SeeThrough::Mask => {
+                // bars every 8 pixels both ways, scaled to a 32 pixel wall
+                let top = horizon.saturating_sub(self.face.from_axis);
+                let v = (y - top) * 32 / (2 * self.face.from_axis).max(1);
+                self.u % 8 < 2 || v % 8 < 2
             }
         }
-        for y in 512 / 2 + from_axis..512 as u32 {
-            // floor
-            if let Some(p) = img.get_pixel_mut_checked(x, y) {
-                *p = HORIZONTAL_COLORS[1];
-            }
+    }
+}
+
+#[derive(Clone)]
+struct Column {
+    face: Face,
+    angle: f32,
+    /// How far away the floor is one row from the horizon. `n` rows away
+    /// it's `1 / n` of that.
+    ground: f32,
+    /// Farthest first, so they can be drawn over each other in order.
+    layers: Vec<Layer>,
+}
+
+fn view_pixel(
+    x: u32,
+    y: u32,
+    horizon: u32,
+    col: &Column,
+    cam: &Camera,
+    shading: &Shading,
+) -> (u8, [f32; 3]) {
+    // the nearest see-through wall showing at this pixel wins, otherwise
+    // whatever is behind them all
+    if let Some(layer) = col
+        .layers
+        .iter()
+        .rev()
+        .find(|layer| layer.face.covers(y, horizon) && layer.shows(x, y, horizon))
+    {
+        let face = &layer.face;
+        return (shading.pick(face.ramp, face.shade, x, y), face.tint);
+    }
+    let face = &col.face;
+    if face.covers(y, horizon) {
+        return (shading.pick(face.ramp, face.shade, x, y), face.tint);
+    }
+    // the floor or ceiling at this row is as far away as a wall this tall
+    let ceiling = y < horizon;
+    let rows = if ceiling { horizon - y } else { y - horizon };
+    let distance = col.ground / rows.max(1) as f32;
+    let (dark, tint) = shading.light(cam, col.angle, distance);
+    if ceiling || y == horizon {
+        // fogging the ceiling would only take black to black
+        (shading.pick(CEILING_RAMP, dark, x, y), tint)
+    } else {
+        let shade = shading.fog * distance / cam.max_distance + dark;
+        (shading.pick(FLOOR_RAMP, shade, x, y), tint)
+    }
+}
+
+fn draw_view_row<C>(
+    row: &mut [C],
+    y: u32,
+    horizon: u32,
+    columns: &[Column],
+    (cam, shading): (&Camera, &Shading),
+    (channels, px): (usize, &impl Fn(u8, [f32; 3], &mut [C])),
+) {
+    // the view is cast right to left, so column i lands at x = w - 1 - i
+    let w = row.len() / channels;
+    for (x, p) in row.chunks_exact_mut(channels).enumerate() {
+        if let Some(col) = columns.get(w - 1 - x) {
+            let (idx, tint) = view_pixel(x as u32, y, horizon, col, cam, shading);
+            px(idx, tint, p);
         }
-        for y in 0..=256_u32.saturating_sub(from_axis) as u32 {
-            // ceiling
-            if let Some(p) = img.get_pixel_mut_checked(x, y) {
-                *p = HORIZONTAL_COLORS[0];
+    }
+}
+
+fn view_columns(view: &[Ray<Wall>], cam: &Camera, shading: &Shading) -> Vec<Column> {
+    view.iter()
+        .map(|ray| {
+            let face = |wall, side, distance| {
+                Face::new(wall, side, ray.angle, distance, (cam, view.len()), shading)
+            };
+            let layers = ray
+                .through
+                .iter()
+                .rev()
+                .map(|hit| Layer {
+                    face: face(Some(hit.wall), hit.side, hit.distance),
+                    see_through: hit.wall.see_through(),
+                    u: if hit.side == Side::X { hit.y } else { hit.x },
+                })
+                .collect();
+            Column {
+                face: face(ray.wall, ray.side, ray.distance),
+                angle: ray.angle,
+                ground: cast::calculate_distance(1.0, ray.angle, cam, view.len()),
+                layers,
             }
-        }
+        })
+        .collect()
+}
+
+fn fill_view<C: Send>(
+    buf: &mut [C],
+    (w, h): (u32, u32),
+    view: &[Ray<Wall>],
+    cam: &Camera,
+    shading: &Shading,
+    (channels, px): (usize, impl Fn(u8, [f32; 3], &mut [C]) + Sync),
+) {
+    // buf holds h rows of w pixels, `channels` entries each. px writes a
+    // palette index and light tint into one pixel
+    let columns = view_columns(view, cam, shading);
+    let horizon = h / 2;
+    let stride = channels * w as usize;
+    if stride == 0 {
+        return;
     }
+    let ctx = (cam, shading);
+    let out = (channels, &px);
+    // every row can be filled on its own, which lets the `parallel`
+    // feature hand them out to threads
+    #[cfg(feature = "parallel")]
+    buf.par_chunks_mut(stride)
+        .enumerate()
+        .for_each(|(y, row)| draw_view_row(row, y as u32, horizon, &columns, ctx, out));
+    #[cfg(not(feature = "parallel"))]
+    buf.chunks_mut(stride)
+        .enumerate()
+        .for_each(|(y, row)| draw_view_row(row, y as u32, horizon, &columns, ctx, out));
 }
 
-pub fn draw_ray(img: &mut image::RgbImage, cam: &Camera, ray: &Ray<Wall>) {
-    // for debug
+pub fn draw_view(img: &mut impl Frame, view: &[Ray<Wall>], cam: &Camera) {
+    draw_view_shaded(img, view, cam, &Shading::default());
+}
+
+pub fn draw_view_shaded<F: Frame>(
+    img: &mut F,
+    view: &[Ray<Wall>],
+    cam: &Camera,
+    shading: &Shading,
+) {
+    let dims = img.dimensions();
+    let px = |idx: u8, tint: [f32; 3], out: &mut [F::Channel]| {
+        let color = PALETTE[idx as usize].0;
+        F::encode(
+            Rgb([0, 1, 2].map(|i| (color[i] as f32 * tint[i]) as u8)),
+            out,
+        );
+    };
+    fill_view(
+        img.channels_mut(),
+        dims,
+        view,
+        cam,
+        shading,
+        (F::CHANNELS, px),
+    );
+}
+
+/// Like `draw_view`, but writes palette indices so the frame can be
+/// resolved with any `Palette` afterwards.
+pub fn draw_view_indexed(img: &mut IndexedImage, view: &[Ray<Wall>], cam: &Camera) {
+    draw_view_indexed_shaded(img, view, cam, &Shading::default());
+}
+
+pub fn draw_view_indexed_shaded(
+    img: &mut IndexedImage,
+    view: &[Ray<Wall>],
+    cam: &Camera,
+    shading: &Shading,
+) {
+    let dims = img.dimensions();
+    fill_view(
+        img,
+        dims,
+        view,
+        cam,
+        shading,
+        (1, |idx, tint, out: &mut [u8]| {
+            out[0] = tinted_index(idx, tint)
+        }),
+    );
+}
+
+pub fn draw_ray(img: &mut impl Frame, cam: &Camera, ray: &Ray<Wall>) {
+    // for debug. segment hits land between steps, so stop at the first
+    // step that reaches the hit rather than one exactly on it
+    let (w, h) = img.dimensions();
     for step in 0..cam.ray_steps {
         let dist = cam.max_distance * (step as f32) / (cam.ray_steps as f32);
-        let offset = cast::calculate_ray(dist, ray.angle);
-        let x_off = offset.0;
-        let y_off = offset.1;
-        let x = (cam.x + x_off) as u32;
-        let y = (cam.y - y_off) as u32;
-        if dist == ray.distance {
+        if dist >= ray.distance {
             break;
         }
-        img.put_pixel(x, y, PALETTE[2]);
+        let (x_off, y_off) = cast::calculate_ray(dist, ray.angle);
+        let (x, y) = (cam.x + x_off, cam.y - y_off);
+        if x >= 0 && y >= 0 && (x as u32) < w && (y as u32) < h {
+            img.put(x as u32, y as u32, PALETTE[2]);
+        }
     }
 }
 
-pub fn draw_fov(img: &mut image::RgbImage, view: &[Ray<Wall>], cam: &Camera) {
+pub fn draw_fov(img: &mut impl Frame, view: &[Ray<Wall>], cam: &Camera) {
     for ray in view {
         draw_ray(img, cam, ray);
     }
 }
 
-pub fn draw_camera(img: &mut image::RgbImage, camera: &Camera) {
+pub fn draw_camera(img: &mut impl Frame, camera: &Camera) {
     // crosshairs for camera location
     for x in camera.x - 10..=camera.x + 10 {
-        img.put_pixel(x as u32, camera.y as u32, PALETTE[0]);
+        img.put(x as u32, camera.y as u32, PALETTE[0]);
     }
     for y in camera.y - 10..=camera.y + 10 {
-        img.put_pixel(camera.x as u32, y as u32, PALETTE[0]);
+        img.put(camera.x as u32, y as u32, PALETTE[0]);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    //use super::*;
+    use super::*;
+    use crate::map;
+
+    #[test]
+    fn view_fills_any_resolution() {
+        let map = map::spooky_map();
+        // looking up the hall, where the walls stay clear of the corners
+        let cam = Camera {
+            x: 240,
+            y: 464,
+            radians: std::f32::consts::FRAC_PI_2,
+            ..Camera::default()
+        };
+        let view = cast::cast_columns(&map, &cam, 1920);
+        let mut img = image::RgbImage::new(1920, 1080);
+        draw_view(&mut img, &view, &cam);
+        assert_eq!(*img.get_pixel(0, 0), PALETTE[7]);
+        assert_eq!(*img.get_pixel(1919, 1079), PALETTE[3]);
+    }
+
+    #[test]
+    fn indexed_matches_rgb() {
+        let map = map::spooky_map();
+        let cam = Camera {
+            x: 240,
+            y: 464,
+            ..Camera::default()
+        };
+        let view = cast::cast_fov(&map, &cam);
+        let mut img = image::RgbImage::new(512, 512);
+        draw_view(&mut img, &view, &cam);
+        let mut indexed = IndexedImage::new(512, 512);
+        draw_view_indexed(&mut indexed, &view, &cam);
+        let mut resolved = image::RgbImage::new(512, 512);
+        crate::palette::Palette::default().apply(&indexed, &mut resolved);
+        assert!(img == resolved);
+    }
+
+    #[test]
+    fn any_frame() {
+        use crate::frame::{Buffer, Framebuffer};
+        let map = map::spooky_map();
+        let cam = Camera {
+            x: 240,
+            y: 464,
+            ..Camera::default()
+        };
+        let view = cast::cast_columns(&map, &cam, 64);
+        let mut img = image::RgbImage::new(64, 48);
+        draw_view(&mut img, &view, &cam);
+        draw_map(&mut img, &map::Map::new(8, 8));
+        let mut buffer = Buffer::new(64, 48);
+        draw_view(&mut buffer, &view, &cam);
+        draw_map(&mut buffer, &map::Map::new(8, 8));
+        let mut words = vec![0; 64 * 48];
+        let mut framebuffer = Framebuffer::new(&mut words, 64, 48);
+        draw_view(&mut framebuffer, &view, &cam);
+        draw_map(&mut framebuffer, &map::Map::new(8, 8));
+        for (x, y) in [(0, 0), (7, 7), (32, 20), (63, 47)] {
+            assert_eq!(buffer.get(x, y), *img.get_pixel(x, y));
+            assert_eq!(framebuffer.get(x, y), *img.get_pixel(x, y));
+        }
+        assert!(img.pixels().zip(&buffer.pixels).all(|(a, b)| a == b));
+    }
+
+    #[test]
+    fn shading_ramps() {
+        let flat = Shading::default();
+        assert_eq!(flat.pick(&[0, 1, 2, 3, 7], 0.3, 0, 0), 1);
+        assert_eq!(flat.pick(&[0, 1, 2, 3, 7], 2.0, 0, 0), 7);
+        assert_eq!(flat.pick(&[6, 7], 0.0, 0, 0), 6);
+
+        // halfway between two entries, a 4x4 block is dithered half and half
+        let bayer = Shading {
+            dither: Dither::Bayer,
+            ..flat
+        };
+        let dark = (0..16)
+            .filter(|i| bayer.pick(&[6, 7], 0.5, i % 4, i / 4) == 7)
+            .count();
+        assert_eq!(dark, 8);
+        let dark = (0..16)
+            .filter(|i| bayer.pick(&[6, 7], 0.25, i % 4, i / 4) == 7)
+            .count();
+        assert_eq!(dark, 4);
+    }
+
+    #[test]
+    fn fog_darkens_far_walls() {
+        // an empty hall with a stone wall 440 pixels away
+        let mut map = map::Map::new(512, 512);
+        for y in 0..512 {
+            map.map[500 + y * 512] = Some(Wall::Stone);
+        }
+        let cam = Camera {
+            x: 60,
+            y: 256,
+            ..Camera::default()
+        };
+        let view = cast::cast_fov(&map, &cam);
+        let shading = Shading {
+            fog: 1.0,
+            ..Shading::default()
+        };
+        let mut flat = image::RgbImage::new(512, 512);
+        draw_view(&mut flat, &view, &cam);
+        let mut foggy = image::RgbImage::new(512, 512);
+        draw_view_shaded(&mut foggy, &view, &cam, &shading);
+        assert_eq!(*flat.get_pixel(256, 256), PALETTE[6]);
+        assert_eq!(*foggy.get_pixel(256, 256), PALETTE[7]);
+        // close floor keeps its color, floor near the horizon fades out
+        assert_eq!(*foggy.get_pixel(256, 511), PALETTE[3]);
+        assert_eq!(*foggy.get_pixel(256, 280), PALETTE[7]);
+    }
+
+    #[test]
+    fn lights() {
+        let mut map = map::Map::new(512, 512);
+        for y in 0..512 {
+            map.map[500 + y * 512] = Some(Wall::Brick);
+        }
+        map.lights.push(crate::map::Light {
+            x: 480,
+            y: 256,
+            color: Rgb([255, 0, 0]),
+            radius: 100.0,
+            falloff: 0.5,
+            flicker: 0.0,
+        });
+        let lightmap = Lightmap::bake(&map, 8, 0.0);
+        let cam = Camera {
+            x: 60,
+            y: 256,
+            ..Camera::default()
+        };
+        let view = cast::cast_fov(&map, &cam);
+        let mut lit = image::RgbImage::new(512, 512);
+        let shading = Shading {
+            lights: Some(&lightmap),
+            ..Shading::default()
+        };
+        draw_view_shaded(&mut lit, &view, &cam, &shading);
+        // the brick right by the light keeps its color, tinted red
+        assert_eq!(*lit.get_pixel(256, 256), Rgb([PALETTE[0][0], 0, 0]));
+        // out of its reach, the floor under the camera is black
+        assert_eq!(*lit.get_pixel(256, 511), PALETTE[7]);
+
+        let mut indexed = IndexedImage::new(512, 512);
+        draw_view_indexed_shaded(&mut indexed, &view, &cam, &shading);
+        assert_eq!(indexed.get_pixel(256, 511).0, [7]);
+    }
+
+    #[test]
+    fn see_through_walls() {
+        // a thin pane of `front` 140 pixels away, in front of a brick wall
+        // at 440, whose span is rows 241..272
+        let count = |front| {
+            let mut map = map::Map::new(512, 512);
+            for y in 0..512 {
+                map.map[500 + y * 512] = Some(Wall::Brick);
+                for x in 200..204 {
+                    map.map[x + y * 512] = Some(front);
+                }
+            }
+            let cam = Camera {
+                x: 60,
+                y: 256,
+                ..Camera::default()
+            };
+            let view = cast::cast_fov(&map, &cam);
+            let mut img = IndexedImage::new(512, 512);
+            draw_view_indexed(&mut img, &view, &cam);
+            let pixels = &img.as_raw()[244 * 512..268 * 512];
+            let count = |wall| {
+                let idx = pick_index(Some(wall));
+                pixels.iter().filter(|&&p| p == idx).count()
+            };
+            (count(front), count(Wall::Brick))
+        };
+        // the crystal screens off half the brick, the grate's bars less
+        let (crystal, brick) = count(Wall::Crystal);
+        assert_eq!(crystal, brick);
+        let (grate, brick) = count(Wall::Grate);
+        assert!(grate > 0 && brick > grate);
+    }
+
+    #[test]
+    fn golden_view() {
+        // a fixed frame up the hall, which threaded filling has to match as
+        // well as serial
+        let map = map::spooky_map();
+        let cam = Camera {
+            x: 240,
+            y: 464,
+            radians: std::f32::consts::FRAC_PI_2,
+            ..Camera::default()
+        };
+        let view = cast::cast_columns(&map, &cam, 24);
+        let mut img = IndexedImage::new(24, 12);
+        draw_view_indexed(&mut img, &view, &cam);
+        let rows: Vec<String> = img
+            .rows()
+            .map(|row| row.map(|p| char::from(b'0' + p.0[0])).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "777777777777777777777777",
+                "666667777777777777666666",
+                "666666777777777776666666",
+                "666666677777777766666666",
+                "666666666666666666666666",
+                "666666666666666666666666",
+                "666666666666666666666666",
+                "666666666666666666666666",
+                "666666666666666666666666",
+                "666666633333333366666666",
+                "666666333333333336666666",
+                "666663333333333333666666",
+            ]
+        );
+    }
 }
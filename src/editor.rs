@@ -0,0 +1,149 @@
+use crate::map::{Map, Wall};
+
+#[derive(Clone, Copy)]
+struct Edit {
+    idx: usize,
+    before: Option<Wall>,
+    after: Option<Wall>,
+}
+
+/// Paints and erases map cells with a square brush, one undoable stroke
+/// per mouse drag.
+pub struct Editor {
+    pub material: Wall,
+    /// Brush width in map pixels. Strokes snap to a grid this size so
+    /// neighbouring dabs line up into walls.
+    pub brush: u32,
+    stroke: Option<Vec<Edit>>,
+    undo: Vec<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            material: Wall::Stone,
+            brush: 16,
+            stroke: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl Editor {
+    pub fn begin_stroke(&mut self) {
+        self.end_stroke();
+        self.stroke = Some(Vec::new());
+    }
+
+    pub fn end_stroke(&mut self) {
+        if let Some(stroke) = self.stroke.take() {
+            if !stroke.is_empty() {
+                self.undo.push(stroke);
+                self.redo.clear();
+            }
+        }
+    }
+
+    /// Paints the current material at (x, y), or clears it when `erase` is
+    /// set. Returns whether anything changed.
+    pub fn paint(&mut self, map: &mut Map, x: u32, y: u32, erase: bool) -> bool {
+        if self.stroke.is_none() {
+            self.begin_stroke();
+        }
+        let after = if erase { None } else { Some(self.material) };
+        let brush = self.brush.max(1);
+        let (x1, y1) = (x / brush * brush, y / brush * brush);
+        let (x2, y2) = ((x1 + brush).min(map.w), (y1 + brush).min(map.h));
+        let stroke = self.stroke.as_mut().unwrap();
+        let mut changed = false;
+        for y in y1..y2 {
+            for x in x1..x2 {
+                let idx = (x + y * map.w) as usize;
+                let before = map.map[idx];
+                if before != after {
+                    map.map[idx] = after;
+                    stroke.push(Edit { idx, before, after });
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn undo(&mut self, map: &mut Map) -> bool {
+        self.end_stroke();
+        let Some(stroke) = self.undo.pop() else {
+            return false;
+        };
+        for edit in stroke.iter().rev() {
+            map.map[edit.idx] = edit.before;
+        }
+        self.redo.push(stroke);
+        true
+    }
+
+    pub fn redo(&mut self, map: &mut Map) -> bool {
+        self.end_stroke();
+        let Some(stroke) = self.redo.pop() else {
+            return false;
+        };
+        for edit in &stroke {
+            map.map[edit.idx] = edit.after;
+        }
+        self.undo.push(stroke);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map;
+
+    #[test]
+    fn undo_redo() {
+        let mut map = map::spooky_map();
+        let original = map.map.clone();
+        let mut editor = Editor::default();
+
+        editor.begin_stroke();
+        assert!(editor.paint(&mut map, 200, 200, false));
+        assert!(editor.paint(&mut map, 220, 200, false));
+        // painting the same spot twice changes nothing
+        assert!(!editor.paint(&mut map, 221, 201, false));
+        editor.end_stroke();
+        assert_eq!(map.map[(200 + 200 * 512) as usize], Some(Wall::Stone));
+        let painted = map.map.clone();
+
+        editor.material = Wall::Crystal;
+        editor.paint(&mut map, 200, 200, true);
+        editor.end_stroke();
+        assert_eq!(map.map[(200 + 200 * 512) as usize], None);
+
+        assert!(editor.undo(&mut map));
+        assert!(map.map == painted);
+        assert!(editor.undo(&mut map));
+        assert!(map.map == original);
+        assert!(!editor.undo(&mut map));
+
+        assert!(editor.redo(&mut map));
+        assert!(map.map == painted);
+
+        // a new stroke drops what's left to redo
+        editor.paint(&mut map, 0, 100, false);
+        editor.end_stroke();
+        assert!(!editor.redo(&mut map));
+    }
+
+    #[test]
+    fn brush_snaps_and_clips() {
+        let mut map = map::Map::new(40, 40);
+        let mut editor = Editor::default();
+        editor.paint(&mut map, 37, 39, false);
+        let painted = map.map.iter().filter(|c| c.is_some()).count();
+        assert_eq!(painted, 8 * 8);
+        assert_eq!(map.map[32 + 32 * 40], Some(Wall::Stone));
+    }
+}
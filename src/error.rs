@@ -0,0 +1,159 @@
+// What can go wrong loading, saving and showing things. Errors say which
+// file they're about, so the app can print them as they are and the user
+// knows what to fix.
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A text file that doesn't parse: maps, palettes, camera paths, replays
+/// and configs.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based, 0 when the problem is the file as a whole.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file, or the terminal when there's no path.
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// Encoding or saving an image.
+    Image {
+        path: Option<PathBuf>,
+        source: image::ImageError,
+    },
+    Parse {
+        path: PathBuf,
+        source: ParseError,
+    },
+    /// Settings that are out of range or don't fit the map.
+    Config(String),
+    /// Opening a window or drawing into it.
+    Window(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn io(path: impl AsRef<Path>, source: io::Error) -> Self {
+        Error::Io {
+            path: Some(path.as_ref().to_path_buf()),
+            source,
+        }
+    }
+
+    pub fn image(path: impl AsRef<Path>, source: image::ImageError) -> Self {
+        Error::Image {
+            path: Some(path.as_ref().to_path_buf()),
+            source,
+        }
+    }
+
+    pub fn parse(path: impl AsRef<Path>, source: ParseError) -> Self {
+        Error::Parse {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {source}", path.display()),
+            Error::Io { path: None, source } => write!(f, "{source}"),
+            Error::Image {
+                path: Some(path),
+                source,
+            } => write!(f, "couldn't write {}: {source}", path.display()),
+            Error::Image { path: None, source } => write!(f, "couldn't encode image: {source}"),
+            Error::Parse { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::Config(message) => write!(f, "{message}"),
+            Error::Window(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::Parse { source, .. } => Some(source),
+            Error::Config(_) | Error::Window(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { path: None, source }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(source: image::ImageError) -> Self {
+        Error::Image { path: None, source }
+    }
+}
+
+/// Reads and parses one of the crate's text files.
+pub(crate) fn load<T: std::str::FromStr<Err = ParseError>>(path: &Path) -> Result<T> {
+    let text = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    text.parse().map_err(|e| Error::parse(path, e))
+}
+
+/// Writes a text file, making its directory if it isn't there.
+pub(crate) fn save(path: &Path, contents: &str) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+    }
+    std::fs::write(path, contents).map_err(|e| Error::io(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Map;
+
+    #[test]
+    fn messages() {
+        let dir = std::env::temp_dir().join(format!("raycaster-error-{}", std::process::id()));
+        let Err(missing) = Map::load(dir.join("missing.map")) else {
+            panic!("loaded a missing map");
+        };
+        assert!(matches!(missing, Error::Io { .. }));
+        assert!(missing.to_string().contains("missing.map: "));
+
+        let path = dir.join("bad.map");
+        save(&path, "size 4 4\nbogus\n").unwrap();
+        let Err(bad) = Map::load(&path) else {
+            panic!("loaded a bad map");
+        };
+        assert!(matches!(&bad, Error::Parse { source, .. } if source.line == 2));
+        assert!(bad
+            .to_string()
+            .starts_with(&format!("{}: line 2: ", path.display())));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
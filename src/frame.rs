@@ -0,0 +1,155 @@
+// Places to draw into. The view is filled a row at a time straight into the
+// target's own storage, so each kind of target says how it lays out and
+// packs its pixels.
+use image::Rgb;
+
+pub trait Frame {
+    /// What pixels are stored as: bytes for images, whole words for
+    /// framebuffers.
+    type Channel: Copy + Send;
+    /// How many channels make up one pixel.
+    const CHANNELS: usize;
+
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Every pixel's channels, row by row from the top.
+    fn channels_mut(&mut self) -> &mut [Self::Channel];
+
+    /// Writes `color` into one pixel's worth of channels.
+    fn encode(color: Rgb<u8>, out: &mut [Self::Channel]);
+
+    fn get(&self, x: u32, y: u32) -> Rgb<u8>;
+
+    fn put(&mut self, x: u32, y: u32, color: Rgb<u8>) {
+        let (w, h) = self.dimensions();
+        assert!(
+            x < w && y < h,
+            "pixel ({x}, {y}) is outside a {w}x{h} frame"
+        );
+        let i = (x + y * w) as usize * Self::CHANNELS;
+        Self::encode(color, &mut self.channels_mut()[i..i + Self::CHANNELS]);
+    }
+}
+
+impl Frame for image::RgbImage {
+    type Channel = u8;
+    const CHANNELS: usize = 3;
+
+    fn dimensions(&self) -> (u32, u32) {
+        image::RgbImage::dimensions(self)
+    }
+
+    fn channels_mut(&mut self) -> &mut [u8] {
+        self
+    }
+
+    fn encode(color: Rgb<u8>, out: &mut [u8]) {
+        out.copy_from_slice(&color.0);
+    }
+
+    fn get(&self, x: u32, y: u32) -> Rgb<u8> {
+        *self.get_pixel(x, y)
+    }
+}
+
+/// A window's framebuffer, like softbuffer's, with a pixel per `u32` as
+/// `0RGB`.
+pub struct Framebuffer<'a> {
+    pub pixels: &'a mut [u32],
+    pub w: u32,
+    pub h: u32,
+}
+
+impl<'a> Framebuffer<'a> {
+    /// `pixels` has to hold at least `w * h` of them.
+    pub fn new(pixels: &'a mut [u32], w: u32, h: u32) -> Self {
+        assert!(
+            pixels.len() >= (w * h) as usize,
+            "{w}x{h} framebuffer is too short"
+        );
+        Self { pixels, w, h }
+    }
+}
+
+impl Frame for Framebuffer<'_> {
+    type Channel = u32;
+    const CHANNELS: usize = 1;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.w, self.h)
+    }
+
+    fn channels_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels[..(self.w * self.h) as usize]
+    }
+
+    fn encode(Rgb([r, g, b]): Rgb<u8>, out: &mut [u32]) {
+        out[0] = (r as u32) << 16 | (g as u32) << 8 | b as u32;
+    }
+
+    fn get(&self, x: u32, y: u32) -> Rgb<u8> {
+        let [_, r, g, b] = self.pixels[(x + y * self.w) as usize].to_be_bytes();
+        Rgb([r, g, b])
+    }
+}
+
+/// A plain grid of colors, for tests and anything else that just wants to
+/// look at the result.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Buffer {
+    pub w: u32,
+    pub h: u32,
+    pub pixels: Vec<Rgb<u8>>,
+}
+
+impl Buffer {
+    pub fn new(w: u32, h: u32) -> Self {
+        Self {
+            w,
+            h,
+            pixels: vec![Rgb([0, 0, 0]); (w * h) as usize],
+        }
+    }
+}
+
+impl Frame for Buffer {
+    type Channel = Rgb<u8>;
+    const CHANNELS: usize = 1;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.w, self.h)
+    }
+
+    fn channels_mut(&mut self) -> &mut [Rgb<u8>] {
+        &mut self.pixels
+    }
+
+    fn encode(color: Rgb<u8>, out: &mut [Rgb<u8>]) {
+        out[0] = color;
+    }
+
+    fn get(&self, x: u32, y: u32) -> Rgb<u8> {
+        self.pixels[(x + y * self.w) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(frame: &mut impl Frame) {
+        let color = Rgb([12, 34, 56]);
+        frame.put(3, 1, color);
+        assert_eq!(frame.get(3, 1), color);
+        assert_eq!(frame.get(2, 1), Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn targets() {
+        round_trip(&mut image::RgbImage::new(4, 2));
+        round_trip(&mut Buffer::new(4, 2));
+        let mut words = vec![0; 10];
+        round_trip(&mut Framebuffer::new(&mut words, 4, 2));
+        assert_eq!(words[7], 0x0c2238);
+    }
+}
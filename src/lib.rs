@@ -1,3 +1,17 @@
+pub mod actor;
 pub mod cast;
+pub mod config;
 pub mod draw;
+pub mod editor;
+pub mod error;
+pub mod frame;
+pub mod light;
 pub mod map;
+pub mod palette;
+pub mod record;
+pub mod replay;
+pub mod sight;
+pub mod sprite;
+pub mod term;
+pub mod trace;
+pub mod world;
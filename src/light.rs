@@ -0,0 +1,181 @@
+use crate::cast;
+use crate::map::{Light, Map, SeeThrough};
+
+/// Light levels over a map, one per `cell` by `cell` pixel square. Each
+/// light's reach is baked once, occlusion included, so flickering only
+/// needs `update` to re-sum them.
+pub struct Lightmap {
+    pub cell: u32,
+    pub w: u32,
+    pub h: u32,
+    /// Light everywhere gets, even with no lights in sight.
+    pub ambient: f32,
+    lights: Vec<Light>,
+    // per light, how much of it reaches each cell
+    reach: Vec<Vec<f32>>,
+    levels: Vec<[f32; 3]>,
+}
+
+fn occluded(map: &Map, from: (f32, f32), to: (f32, f32), slack: f32) -> bool {
+    // step a pixel at a time from the light, stopping `slack` short of the
+    // target so walls are lit on the side facing the light
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let steps = (length - slack).max(0.0) as u32;
+    for step in 1..steps {
+        let t = step as f32 / length;
+        let (x, y) = (from.0 + dx * t, from.1 + dy * t);
+        if x < 0.0 || y < 0.0 || x >= map.w as f32 || y >= map.h as f32 {
+            return true;
+        }
+        // see-through walls let the light past
+        let cell = map.map[(x as u32 + y as u32 * map.w) as usize];
+        if cell.is_some_and(|wall| wall.see_through() == SeeThrough::Opaque) {
+            return true;
+        }
+    }
+    // segments are too thin for the steps to be sure of landing on, so
+    // they're crossed exactly. the angle has +y up, like the camera's
+    let angle = (-dy).atan2(dx);
+    map.segments.iter().any(|segment| {
+        segment.wall.see_through() == SeeThrough::Opaque
+            && cast::intersect(from, angle, segment).is_some_and(|d| d < steps as f32)
+    })
+}
+
+impl Lightmap {
+    pub fn bake(map: &Map, cell: u32, ambient: f32) -> Self {
+        let cell = cell.max(1);
+        let (w, h) = (map.w.div_ceil(cell), map.h.div_ceil(cell));
+        let reach = map
+            .lights
+            .iter()
+            .map(|light| {
+                let from = (light.x as f32, light.y as f32);
+                let mut reach = vec![0.0; (w * h) as usize];
+                for cy in 0..h {
+                    for cx in 0..w {
+                        let to = (
+                            (cx as f32 + 0.5) * cell as f32,
+                            (cy as f32 + 0.5) * cell as f32,
+                        );
+                        let d = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+                        if d >= light.radius || occluded(map, from, to, cell as f32) {
+                            continue;
+                        }
+                        reach[(cx + cy * w) as usize] =
+                            (1.0 - d / light.radius).powf(light.falloff);
+                    }
+                }
+                reach
+            })
+            .collect();
+        let mut lightmap = Self {
+            cell,
+            w,
+            h,
+            ambient,
+            lights: map.lights.clone(),
+            reach,
+            levels: vec![[ambient; 3]; (w * h) as usize],
+        };
+        lightmap.update(0.0);
+        lightmap
+    }
+
+    /// Re-sums the lights for time `t` seconds, for flickering.
+    pub fn update(&mut self, t: f32) {
+        self.levels.fill([self.ambient; 3]);
+        for (light, reach) in self.lights.iter().zip(&self.reach) {
+            let intensity = light.intensity(t);
+            let color = light.color.0.map(|c| c as f32 / 255.0 * intensity);
+            for (level, amount) in self.levels.iter_mut().zip(reach) {
+                for i in 0..3 {
+                    level[i] += color[i] * amount;
+                }
+            }
+        }
+    }
+
+    pub fn flickers(&self) -> bool {
+        self.lights.iter().any(|light| light.flicker > 0.0)
+    }
+
+    /// RGB light level at map pixel (x, y). 1.0 is full brightness, and
+    /// it can go over where lights overlap.
+    pub fn at(&self, x: f32, y: f32) -> [f32; 3] {
+        if x < 0.0 || y < 0.0 {
+            return [self.ambient; 3];
+        }
+        let (cx, cy) = (x as u32 / self.cell, y as u32 / self.cell);
+        if cx >= self.w || cy >= self.h {
+            return [self.ambient; 3];
+        }
+        self.levels[(cx + cy * self.w) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Segment, Wall};
+    use image::Rgb;
+
+    fn room() -> Map {
+        // 128x64 with a wall down the middle from y = 0 to 48
+        let mut map = Map::new(128, 64);
+        for y in 0..48 {
+            for x in 60..68 {
+                map.map[x + y * 128] = Some(Wall::Stone);
+            }
+        }
+        map.lights.push(Light {
+            x: 30,
+            y: 20,
+            color: Rgb([255, 0, 0]),
+            radius: 100.0,
+            falloff: 1.0,
+            flicker: 0.0,
+        });
+        map
+    }
+
+    #[test]
+    fn falloff_and_occlusion() {
+        let lightmap = Lightmap::bake(&room(), 8, 0.1);
+        let near = lightmap.at(30.0, 20.0);
+        let far = lightmap.at(50.0, 40.0);
+        assert!(near[0] > far[0] && far[0] > 0.1);
+        assert_eq!(near[1], 0.1);
+        // straight through the wall is dark, around its open end is lit
+        assert_eq!(lightmap.at(90.0, 20.0), [0.1; 3]);
+        assert!(lightmap.at(68.0, 60.0)[0] > 0.1);
+        // the wall's own face towards the light picks some up
+        assert!(lightmap.at(61.0, 20.0)[0] > 0.1);
+        assert!(!lightmap.flickers());
+
+        // a thin wall shades what's behind it, unless it's see-through
+        let mut map = room();
+        map.segments
+            .push(Segment::vertical(0.0, 64.0, 40.0, Wall::Brick));
+        assert_eq!(Lightmap::bake(&map, 8, 0.1).at(50.0, 40.0), [0.1; 3]);
+        map.segments[0].wall = Wall::Crystal;
+        assert_eq!(Lightmap::bake(&map, 8, 0.1).at(50.0, 40.0), far);
+    }
+
+    #[test]
+    fn flicker() {
+        let mut map = room();
+        map.lights[0].flicker = 1.0;
+        let mut lightmap = Lightmap::bake(&map, 8, 0.0);
+        assert!(lightmap.flickers());
+        let levels: Vec<f32> = (0..20)
+            .map(|i| {
+                lightmap.update(i as f32 * 0.1);
+                lightmap.at(30.0, 20.0)[0]
+            })
+            .collect();
+        assert!(levels.iter().all(|l| (0.0..=1.0).contains(l)));
+        assert!(levels.windows(2).any(|w| w[0] != w[1]));
+    }
+}
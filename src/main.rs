@@ -1,63 +1,279 @@
-use std::f32::consts::PI;
+use std::env;
 use std::fs;
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant};
 
+use image::Rgb;
 use std::num::NonZeroU32;
 use std::rc::Rc;
 use winit::dpi::PhysicalSize;
-use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
+use winit::event::{ElementState, Event, KeyEvent, MouseButton, StartCause, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::keyboard::{Key, KeyCode, NamedKey, PhysicalKey};
 use winit::window::WindowBuilder;
 
-use raycaster::cast::{self, Camera};
-use raycaster::draw;
-use raycaster::map;
+use raycaster::config::Config;
+use raycaster::draw::{Dither, Shading};
+use raycaster::editor::Editor;
+use raycaster::error::{Error, Result};
+use raycaster::map::{self, Wall};
+use raycaster::palette::{IndexedImage, Palette};
+use raycaster::record::{self, CameraPath};
+use raycaster::replay::Recording;
+use raycaster::term;
+use raycaster::world::{Input, World, Zoom};
 
-fn write_image(img: &image::RgbImage, fname: &str) {
-    // TODO: handle errors
+fn write_image(img: &image::RgbImage, fname: &str) -> Result<()> {
     // make the output directory if it doesn't already exist
-    let _ = fs::create_dir("output");
-    // write the image
-    let kind = image::ColorType::Rgb8;
-    let dims = img.dimensions();
-    let _ = image::save_buffer("output/".to_string() + fname, img, dims.0, dims.1, kind);
+    fs::create_dir_all("output").map_err(|e| Error::io("output", e))?;
+    let path = Path::new("output").join(fname);
+    img.save(&path).map_err(|e| Error::image(&path, e))
 }
 
-fn main() {
-    let w: u32 = 512;
-    let h: u32 = 512;
-    let mut img = image::RgbImage::new(w, h);
-    let mut render = image::RgbImage::new(w, h);
+fn load_map(name: &str) -> Result<map::Map> {
+    // a built-in map, or the path to a map file
+    match name {
+        "spooky" => Ok(map::spooky_map()),
+        "test" => Ok(map::gen_map(512, 512)),
+        path => map::Map::load(path),
+    }
+}
+
+fn material_key(code: KeyCode) -> Option<Wall> {
+    match code {
+        KeyCode::Digit1 => Some(Wall::ALL[0]),
+        KeyCode::Digit2 => Some(Wall::ALL[1]),
+        KeyCode::Digit3 => Some(Wall::ALL[2]),
+        KeyCode::Digit4 => Some(Wall::ALL[3]),
+        KeyCode::Digit5 => Some(Wall::ALL[4]),
+        _ => None,
+    }
+}
+
+fn refresh(
+    img: &mut image::RgbImage,
+    render: &mut image::RgbImage,
+    world: &World,
+    palette: &Palette,
+) {
+    world.render_map(img);
+    // the 3D view goes through palette indices so palettes can be swapped
+    let mut frame = IndexedImage::new(render.width(), render.height());
+    world.render(&mut frame);
+    palette.apply(&frame, render);
+}
+
+fn load_config(args: &[String]) -> Result<(Config, Vec<String>)> {
+    // settings come from --config's file, or raycaster.toml if there is
+    // one, with any other flags on top
+    let (path, args) = match args.iter().position(|a| a == "--config") {
+        Some(i) => {
+            let path = args
+                .get(i + 1)
+                .ok_or_else(|| Error::Config("--config needs a value".to_string()))?;
+            let rest = [&args[..i], &args[(i + 2)..]].concat();
+            (Some(path.as_str()), rest)
+        }
+        None => (None, args.to_vec()),
+    };
+    let path = path.or(Path::new("raycaster.toml")
+        .exists()
+        .then_some("raycaster.toml"));
+    let mut config = match path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let rest = config.apply_args(&args)?;
+    config.validate()?;
+    Ok((config, rest))
+}
+
+fn start_world(map: map::Map, config: &Config) -> Result<World> {
+    // problems with the map itself, a spawn in a wall included, only get
+    // a warning, since it can still be walked about
+    for problem in &map.validate().problems {
+        eprintln!("warning: {problem}");
+    }
+    let mut world = World::new(map);
+    world.camera = config.camera(&world.map)?;
+    world.start_fov = world.camera.fov;
+    world.shading = shading();
+    Ok(world)
+}
+
+fn validate(args: &[String], config: &Config) -> Result<i32> {
+    // raycaster validate [map]
+    let name = args.first().unwrap_or(&config.start.map);
+    let report = load_map(name)?.validate();
+    println!("{name}: {report}");
+    Ok(if report.is_ok() { 0 } else { 1 })
+}
+
+fn shading() -> Shading<'static> {
+    Shading {
+        fog: 0.8,
+        side: 0.2,
+        dither: Dither::Bayer,
+        lights: None,
+    }
+}
 
-    //    let map = map::gen_map(512, 512);
-    let map = map::spooky_map();
-    let mut camera = Camera {
-        x: 240,
-        y: 464,
-        radians: 0.5 * PI,
-        ..Camera::default()
+fn term(args: &[String], config: &Config) -> Result<i32> {
+    // raycaster term [map] [palette]
+    let name = args.first().unwrap_or(&config.start.map);
+    let map = load_map(name)?;
+    let palette = match args.get(1) {
+        Some(path) => Palette::load(path)?,
+        None => Palette::default(),
     };
+    let mut world = start_world(map, config)?;
+    term::run(&mut world, &palette)?;
+    Ok(0)
+}
 
-    draw::draw_map(&mut img, &map);
+fn record(args: &[String], config: &Config) -> Result<i32> {
+    // raycaster record <path> [map] [out]
+    let Some(path_file) = args.first() else {
+        eprintln!("usage: raycaster record <path> [map] [out]");
+        return Ok(2);
+    };
+    let path = CameraPath::load(path_file)?;
+    let name = args.get(1).unwrap_or(&config.start.map);
+    let map = load_map(name)?;
+    // the path moves it, the rest of the camera is from the config
+    let cam = config.camera(&map)?;
+    let out = args.get(2).cloned().unwrap_or_else(|| {
+        let stem = Path::new(path_file).file_stem().unwrap_or_default();
+        format!("output/{}.gif", stem.to_string_lossy())
+    });
+    let fps = 15.0;
+    let size = (config.view.width, config.view.height);
+    let shading = shading();
+    let frames = record::render(&map, &path, &cam, size, fps, &shading);
+    let count = record::save(frames, Path::new(&out), fps)?;
+    println!("wrote {count} frames to {out}");
+    Ok(0)
+}
 
-    draw::draw_camera(&mut img, &camera);
+fn replay(args: &[String], config: &Config) -> Result<i32> {
+    // raycaster replay <recording> [map]
+    let Some(file) = args.first() else {
+        eprintln!("usage: raycaster replay <recording> [map]");
+        return Ok(2);
+    };
+    let recording = Recording::load(file)?;
+    // the map named on the command line, then the one it was recorded on
+    let name = args
+        .get(1)
+        .or(recording.map.as_ref())
+        .unwrap_or(&config.start.map);
+    let mut world = start_world(load_map(name)?, config)?;
+    recording.replay(&mut world, |_| {});
+    let cam = &world.camera;
+    println!(
+        "{} ticks: camera at {} {} facing {}",
+        recording.ticks, cam.x, cam.y, cam.radians
+    );
+    let (w, h) = (config.view.width, config.view.height);
+    let mut frame = IndexedImage::new(w, h);
+    world.render(&mut frame);
+    let mut render = image::RgbImage::new(w, h);
+    Palette::default().apply(&frame, &mut render);
+    write_image(&render, "replay.png")?;
+    Ok(0)
+}
 
-    let view = cast::cast_fov(&map, &camera);
-    draw::draw_fov(&mut img, &view, &camera);
+fn play(args: &[String], config: &Config) -> Result<i32> {
+    // raycaster [map] [palette]
+    let name = args.first().unwrap_or(&config.start.map);
+    let map = load_map(name)?;
+    // the editor saves over map files, and built-ins to output/
+    let save_path = match name.as_str() {
+        "spooky" | "test" => format!("output/{name}.map"),
+        path => path.to_string(),
+    };
+    let mut palettes = vec![Palette::default()];
+    if let Some(path) = args.get(1) {
+        palettes.push(Palette::load(path)?);
+    }
+    // the session is saved to output/last.replay on the way out
+    let recording = Recording {
+        map: Some(name.to_string()),
+        ..Recording::default()
+    };
+    let world = start_world(map, config)?;
+    run(world, save_path, palettes, recording, config)?;
+    Ok(0)
+}
 
-    write_image(&img, "map.png");
+fn main() {
+    // raycaster [map] [palette] | raycaster validate [map]
+    // | raycaster term [map] [palette] | raycaster record <path> [map] [out]
+    // | raycaster replay <recording> [map]
+    // each can take --config FILE and the flags in config::FLAGS
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result =
+        load_config(&args).and_then(|(config, args)| match args.first().map(String::as_str) {
+            Some("validate") => validate(&args[1..], &config),
+            Some("term") => term(&args[1..], &config),
+            Some("record") => record(&args[1..], &config),
+            Some("replay") => replay(&args[1..], &config),
+            _ => play(&args, &config),
+        });
+    match result {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("error: {e}");
+            // 2 for something to fix in the files or flags given
+            let code = match e {
+                Error::Parse { .. } | Error::Config(_) => 2,
+                _ => 1,
+            };
+            process::exit(code);
+        }
+    }
+}
 
-    draw::draw_view(&mut render, &view, &camera);
+fn run(
+    mut world: World,
+    save_path: String,
+    palettes: Vec<Palette>,
+    mut recording: Recording,
+    config: &Config,
+) -> Result<()> {
+    // the 3D view on the left and the map on the right
+    let (view_w, view_h) = (config.view.width, config.view.height);
+    let (map_w, map_h) = (world.map.w, world.map.h);
+    let mut img = image::RgbImage::new(map_w, map_h);
+    let mut render = image::RgbImage::new(view_w, view_h);
 
-    write_image(&render, "render.png");
+    // P cycles through the palettes, starting from the one given on the
+    // command line, N toggles night and B toggles dithering
+    let mut current = palettes.len() - 1;
+    let mut night = false;
+    let mut palette = palettes[current].clone();
+
+    refresh(&mut img, &mut render, &world, &palette);
+    for (img, name) in [(&img, "map.png"), (&render, "render.png")] {
+        if let Err(e) = write_image(img, name) {
+            eprintln!("warning: {e}");
+        }
+    }
 
-    let event_loop = EventLoop::new().unwrap();
+    // without a display there's the terminal front end instead
+    let no_window = |e: &dyn std::fmt::Display| {
+        Error::Window(format!(
+            "couldn't open a window: {e}\n`raycaster term` plays in the terminal instead"
+        ))
+    };
+    let event_loop = EventLoop::new().map_err(|e| no_window(&e))?;
     let window = Rc::new(
         WindowBuilder::new()
-            .with_inner_size(PhysicalSize::new(1028, 512))
+            .with_inner_size(PhysicalSize::new(config.window.width, config.window.height))
             .with_title("Raycaster")
             .build(&event_loop)
-            .unwrap(),
+            .map_err(|e| no_window(&e))?,
     );
 
     #[cfg(target_arch = "wasm32")]
@@ -74,14 +290,51 @@ fn main() {
             .unwrap();
     }
 
-    let context = softbuffer::Context::new(window.clone()).unwrap();
-    let mut surface = softbuffer::Surface::new(&context, window.clone()).unwrap();
+    let context = softbuffer::Context::new(window.clone()).map_err(|e| no_window(&e))?;
+    let mut surface =
+        softbuffer::Surface::new(&context, window.clone()).map_err(|e| no_window(&e))?;
+
+    // editor mode: E toggles, 1-5 pick a material, left mouse paints and
+    // right mouse erases on the map view, ctrl+Z/Y undo and redo, ctrl+S
+    // saves
+    let mut editing = false;
+    let mut editor = Editor::default();
+    let mut ctrl = false;
+    let mut cursor: Option<(u32, u32)> = None;
+    let mut painting: Option<bool> = None; // Some(erase) while a button is held
+
+    // WASD are held down to move, and the world steps every tick while
+    // they are or while it animates by itself. ticks are always the same
+    // length so the recording replays exactly
+    let tick = Duration::from_secs_f32(recording.tick);
+    let mut input = Input::default();
+    let mut last_tick = Instant::now();
+    let mut edited = false;
+    // what stopped the loop, if it wasn't the user
+    let mut failure = None;
+    let failed = &mut failure;
 
     event_loop
         .run(move |event, elwt| {
-            elwt.set_control_flow(ControlFlow::Wait);
+            if world.animates() || input.any() {
+                elwt.set_control_flow(ControlFlow::WaitUntil(last_tick + tick));
+            } else {
+                elwt.set_control_flow(ControlFlow::Wait);
+            }
 
             match event {
+                Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                    last_tick += tick;
+                    if last_tick.elapsed() > 4 * tick {
+                        // too far behind to catch up, drop the time
+                        last_tick = Instant::now();
+                    }
+                    recording.step(&mut world, &input);
+                    input.zoom = None;
+                    refresh(&mut img, &mut render, &world, &palette);
+                    window.request_redraw();
+                }
+
                 Event::WindowEvent {
                     window_id,
                     event: WindowEvent::RedrawRequested,
@@ -90,30 +343,89 @@ fn main() {
                         let size = window.inner_size();
                         (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
                     } {
-                        surface.resize(width, height).unwrap();
-
-                        let mut buffer = surface.buffer_mut().unwrap();
-                        for y in 0..std::cmp::min(512, height.get()) {
-                            for x in 0..std::cmp::min(512, width.get()) {
-                                // TODO this is terrible
-                                let red = render.get_pixel(x, y)[0] as u32;
-                                let green = render.get_pixel(x, y)[1] as u32;
-                                let blue = render.get_pixel(x, y)[2] as u32;
-                                let index = y as usize * width.get() as usize + x as usize;
-                                buffer[index] = blue | (green << 8) | (red << 16);
-                            }
-                            for x in
-                                std::cmp::min(512, width.get())..std::cmp::min(512 * 2, width.get())
-                            {
-                                let red = img.get_pixel(x - 512, y)[0] as u32;
-                                let green = img.get_pixel(x - 512, y)[1] as u32;
-                                let blue = img.get_pixel(x - 512, y)[2] as u32;
-                                let index = y as usize * width.get() as usize + x as usize;
-                                buffer[index] = blue | (green << 8) | (red << 16);
+                        let drawn = surface.resize(width, height).and_then(|()| {
+                            let mut buffer = surface.buffer_mut()?;
+                            // anything the view and map don't cover stays black
+                            buffer.fill(0);
+                            let (width, height) = (width.get(), height.get());
+                            for y in 0..height.min(view_h.max(map_h)) {
+                                for x in 0..width.min(view_w + map_w) {
+                                    // TODO this is terrible
+                                    let pixel = if x < view_w {
+                                        (y < view_h).then(|| render.get_pixel(x, y))
+                                    } else {
+                                        (y < map_h).then(|| img.get_pixel(x - view_w, y))
+                                    };
+                                    let Some(&Rgb([red, green, blue])) = pixel else {
+                                        continue;
+                                    };
+                                    let index = y as usize * width as usize + x as usize;
+                                    buffer[index] =
+                                        blue as u32 | (green as u32) << 8 | (red as u32) << 16;
+                                }
                             }
+
+                            buffer.present()
+                        });
+                        if let Err(e) = drawn {
+                            *failed = Some(Error::Window(format!("couldn't draw the window: {e}")));
+                            elwt.exit();
                         }
+                    }
+                }
 
-                        buffer.present().unwrap();
+                Event::WindowEvent {
+                    event: WindowEvent::ModifiersChanged(modifiers),
+                    ..
+                } => {
+                    ctrl = modifiers.state().control_key();
+                }
+
+                Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    window_id,
+                } if window_id == window.id() => {
+                    // the map view sits to the right of the 3D view
+                    let (x, y) = (position.x as i64 - view_w as i64, position.y as i64);
+                    let (map_w, map_h) = (world.map.w as i64, world.map.h as i64);
+                    cursor = if (0..map_w).contains(&x) && (0..map_h).contains(&y) {
+                        Some((x as u32, y as u32))
+                    } else {
+                        None
+                    };
+                    if let (Some(erase), Some((x, y))) = (painting, cursor) {
+                        if editor.paint(&mut world.map, x, y, erase) {
+                            refresh(&mut img, &mut render, &world, &palette);
+                            window.request_redraw();
+                        }
+                    }
+                }
+
+                Event::WindowEvent {
+                    event: WindowEvent::MouseInput { state, button, .. },
+                    window_id,
+                } if window_id == window.id() && editing => {
+                    let erase = match button {
+                        MouseButton::Left => false,
+                        MouseButton::Right => true,
+                        _ => return,
+                    };
+                    if state == ElementState::Released {
+                        painting = None;
+                        editor.end_stroke();
+                        world.map_changed();
+                        edited = true;
+                        refresh(&mut img, &mut render, &world, &palette);
+                        window.request_redraw();
+                        return;
+                    }
+                    painting = Some(erase);
+                    editor.begin_stroke();
+                    if let Some((x, y)) = cursor {
+                        if editor.paint(&mut world.map, x, y, erase) {
+                            refresh(&mut img, &mut render, &world, &palette);
+                            window.request_redraw();
+                        }
                     }
                 }
 
@@ -122,7 +434,7 @@ fn main() {
                         WindowEvent::KeyboardInput {
                             event:
                                 KeyEvent {
-                                    physical_key: PhysicalKey::Code(KeyCode::KeyA),
+                                    physical_key: PhysicalKey::Code(code),
                                     repeat: false,
                                     state: ElementState::Pressed,
                                     ..
@@ -130,13 +442,41 @@ fn main() {
                             ..
                         },
                     window_id,
-                } if window_id == window.id() => {
-                    camera.radians += 0.125 * PI;
-                    draw::draw_map(&mut img, &map);
-                    draw::draw_camera(&mut img, &camera);
-                    let view = cast::cast_fov(&map, &camera);
-                    draw::draw_fov(&mut img, &view, &camera);
-                    draw::draw_view(&mut render, &view, &camera);
+                } if window_id == window.id()
+                    && (code == KeyCode::KeyE
+                        || editing && (ctrl || material_key(code).is_some())) =>
+                {
+                    if let Some(material) = material_key(code) {
+                        editor.material = material;
+                    }
+                    match code {
+                        KeyCode::KeyE => {
+                            editing = !editing;
+                            painting = None;
+                            editor.end_stroke();
+                        }
+                        KeyCode::KeyZ => {
+                            editor.undo(&mut world.map);
+                            world.map_changed();
+                            edited = true;
+                        }
+                        KeyCode::KeyY => {
+                            editor.redo(&mut world.map);
+                            world.map_changed();
+                            edited = true;
+                        }
+                        KeyCode::KeyS => match world.map.save(&save_path) {
+                            Ok(()) => println!("saved {save_path}"),
+                            Err(e) => eprintln!("couldn't save: {e}"),
+                        },
+                        _ => {}
+                    }
+                    if editing {
+                        window.set_title(&format!("Raycaster - editing {:?}", editor.material));
+                    } else {
+                        window.set_title("Raycaster");
+                    }
+                    refresh(&mut img, &mut render, &world, &palette);
                     window.request_redraw();
                 }
 
@@ -145,7 +485,10 @@ fn main() {
                         WindowEvent::KeyboardInput {
                             event:
                                 KeyEvent {
-                                    physical_key: PhysicalKey::Code(KeyCode::KeyD),
+                                    physical_key:
+                                        PhysicalKey::Code(
+                                            code @ (KeyCode::KeyP | KeyCode::KeyN | KeyCode::KeyB),
+                                        ),
                                     repeat: false,
                                     state: ElementState::Pressed,
                                     ..
@@ -154,12 +497,21 @@ fn main() {
                         },
                     window_id,
                 } if window_id == window.id() => {
-                    camera.radians -= 0.125 * PI;
-                    draw::draw_map(&mut img, &map);
-                    draw::draw_camera(&mut img, &camera);
-                    let view = cast::cast_fov(&map, &camera);
-                    draw::draw_fov(&mut img, &view, &camera);
-                    draw::draw_view(&mut render, &view, &camera);
+                    match code {
+                        KeyCode::KeyP => current = (current + 1) % palettes.len(),
+                        KeyCode::KeyN => night = !night,
+                        _ => {
+                            world.shading.dither = match world.shading.dither {
+                                Dither::Off => Dither::Bayer,
+                                Dither::Bayer => Dither::Off,
+                            }
+                        }
+                    }
+                    palette = palettes[current].clone();
+                    if night {
+                        palette = palette.tinted(Rgb([16, 16, 48]), 0.6);
+                    }
+                    refresh(&mut img, &mut render, &world, &palette);
                     window.request_redraw();
                 }
 
@@ -168,8 +520,14 @@ fn main() {
                         WindowEvent::KeyboardInput {
                             event:
                                 KeyEvent {
-                                    physical_key: PhysicalKey::Code(KeyCode::KeyW),
-                                    repeat: false,
+                                    physical_key:
+                                        PhysicalKey::Code(
+                                            code @ (KeyCode::Equal
+                                            | KeyCode::Minus
+                                            | KeyCode::Digit0
+                                            | KeyCode::BracketLeft
+                                            | KeyCode::BracketRight),
+                                        ),
                                     state: ElementState::Pressed,
                                     ..
                                 },
@@ -177,14 +535,23 @@ fn main() {
                         },
                     window_id,
                 } if window_id == window.id() => {
-                    camera.x += (10.0 * camera.radians.cos()) as i32;
-                    camera.y -= (10.0 * camera.radians.sin()) as i32;
-                    draw::draw_map(&mut img, &map);
-                    draw::draw_camera(&mut img, &camera);
-                    let view = cast::cast_fov(&map, &camera);
-                    draw::draw_fov(&mut img, &view, &camera);
-                    draw::draw_view(&mut render, &view, &camera);
-                    window.request_redraw();
+                    // = and - zoom in and out, 0 puts the fov back, [ and ]
+                    // see nearer and further. it goes in with the next tick
+                    // so the recording has it, straight away if idle
+                    input.zoom = Some(match code {
+                        KeyCode::Equal => Zoom::In,
+                        KeyCode::Minus => Zoom::Out,
+                        KeyCode::Digit0 => Zoom::Reset,
+                        KeyCode::BracketLeft => Zoom::Nearer,
+                        _ => Zoom::Further,
+                    });
+                    if !world.animates() && !input.any() {
+                        last_tick = Instant::now();
+                        recording.step(&mut world, &input);
+                        input.zoom = None;
+                        refresh(&mut img, &mut render, &world, &palette);
+                        window.request_redraw();
+                    }
                 }
 
                 Event::WindowEvent {
@@ -192,23 +559,32 @@ fn main() {
                         WindowEvent::KeyboardInput {
                             event:
                                 KeyEvent {
-                                    physical_key: PhysicalKey::Code(KeyCode::KeyS),
+                                    physical_key:
+                                        PhysicalKey::Code(
+                                            code @ (KeyCode::KeyW
+                                            | KeyCode::KeyA
+                                            | KeyCode::KeyS
+                                            | KeyCode::KeyD),
+                                        ),
                                     repeat: false,
-                                    state: ElementState::Pressed,
+                                    state,
                                     ..
                                 },
                             ..
                         },
                     window_id,
                 } if window_id == window.id() => {
-                    camera.x -= (10.0 * camera.radians.cos()) as i32;
-                    camera.y += (10.0 * camera.radians.sin()) as i32;
-                    draw::draw_map(&mut img, &map);
-                    draw::draw_camera(&mut img, &camera);
-                    let view = cast::cast_fov(&map, &camera);
-                    draw::draw_fov(&mut img, &view, &camera);
-                    draw::draw_view(&mut render, &view, &camera);
-                    window.request_redraw();
+                    let held = state == ElementState::Pressed;
+                    if held && !input.any() && !world.animates() {
+                        // the world was idle, don't count the wait as time
+                        last_tick = Instant::now();
+                    }
+                    match code {
+                        KeyCode::KeyW => input.forward = held,
+                        KeyCode::KeyS => input.back = held,
+                        KeyCode::KeyA => input.turn_left = held,
+                        _ => input.turn_right = held,
+                    }
                 }
 
                 Event::WindowEvent {
@@ -224,10 +600,18 @@ fn main() {
                         },
                     window_id,
                 } if window_id == window.id() => {
+                    match recording.save("output/last.replay") {
+                        Ok(()) if edited => {
+                            println!("saved output/last.replay, but map edits aren't in it")
+                        }
+                        Ok(()) => println!("saved output/last.replay"),
+                        Err(e) => eprintln!("couldn't save the replay: {e}"),
+                    }
                     elwt.exit();
                 }
                 _ => {}
             }
         })
-        .unwrap();
+        .map_err(|e| Error::Window(format!("the window's event loop failed: {e}")))?;
+    failure.map_or(Ok(()), Err)
 }
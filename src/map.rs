@@ -1,24 +1,158 @@
+mod entity;
+mod file;
+pub mod generate;
+mod segment;
+mod validate;
+
+pub use entity::{Light, Marker, Spawn, Thing, ThingKind, Trigger};
+pub use file::ParseError;
+pub use segment::Segment;
+pub use validate::{Problem, Report};
+
 pub struct Map {
     pub map: Vec<Option<Wall>>,
     pub w: u32,
     pub h: u32,
+    pub spawn: Spawn,
+    pub things: Vec<Thing>,
+    pub triggers: Vec<Trigger>,
+    pub markers: Vec<Marker>,
+    pub lights: Vec<Light>,
+    /// Thin walls, on top of the cells.
+    pub segments: Vec<Segment>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Wall {
     Dirt,
     Brick,
     Stone,
     Crystal,
+    Grate,
+}
+
+/// How much of what's behind a wall shows through it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SeeThrough {
+    Opaque,
+    /// Covers this fraction of what's behind, like tinted glass.
+    Alpha(f32),
+    /// Solid bars with gaps between them, like a grate or fence.
+    Mask,
+}
+
+impl Wall {
+    pub const ALL: [Wall; 5] = [
+        Wall::Dirt,
+        Wall::Brick,
+        Wall::Stone,
+        Wall::Crystal,
+        Wall::Grate,
+    ];
+
+    /// How the wall is written in map files.
+    pub fn symbol(self) -> char {
+        match self {
+            Wall::Dirt => 'd',
+            Wall::Brick => 'b',
+            Wall::Stone => 's',
+            Wall::Crystal => 'c',
+            Wall::Grate => 'g',
+        }
+    }
+
+    pub fn see_through(self) -> SeeThrough {
+        match self {
+            Wall::Crystal => SeeThrough::Alpha(0.5),
+            Wall::Grate => SeeThrough::Mask,
+            _ => SeeThrough::Opaque,
+        }
+    }
+
+    pub fn from_symbol(c: char) -> Option<Wall> {
+        Wall::ALL.into_iter().find(|wall| wall.symbol() == c)
+    }
+}
+
+/// Anything rays can be cast through: a `w` by `h` grid of cells, addressed
+/// with +x right and +y down.
+pub trait Grid {
+    type Cell: Copy;
+
+    fn dimensions(&self) -> (u32, u32);
+
+    /// The cell at (x, y), or `None` when it's empty or off the grid.
+    fn cell(&self, x: u32, y: u32) -> Option<Self::Cell>;
+
+    /// Whether a ray stops at this cell. Cells that aren't solid (floor
+    /// tiles, say) are looked through.
+    fn is_solid(&self, _cell: &Self::Cell) -> bool {
+        true
+    }
+
+    /// Whether a ray carries on past this solid cell, noting it on the way.
+    fn is_transparent(&self, _cell: &Self::Cell) -> bool {
+        false
+    }
+
+    /// Thin walls between cells rather than in them, as lines in the same
+    /// space as the grid. `is_transparent` still goes for these.
+    fn segments(&self) -> &[Segment<Self::Cell>] {
+        &[]
+    }
+}
+
+impl Grid for Map {
+    type Cell = Wall;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.w, self.h)
+    }
+
+    fn cell(&self, x: u32, y: u32) -> Option<Wall> {
+        if x >= self.w || y >= self.h {
+            return None;
+        }
+        self.map[(x + y * self.w) as usize]
+    }
+
+    fn is_transparent(&self, cell: &Wall) -> bool {
+        cell.see_through() != SeeThrough::Opaque
+    }
+
+    fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
 }
 
 impl Map {
     const WALL_THICKNESS: u32 = 32;
 
+    /// The widest and tallest map files can be. Cells are indexed with
+    /// `x + y * w` in u32, so `w * h` has to fit.
+    pub const MAX_SIZE: u32 = 8192;
+
+    /// An empty `w` by `h` map. Panics if `w * h` doesn't fit in a u32.
     pub fn new(w: u32, h: u32) -> Self {
-        let mut map = Vec::with_capacity((w * h) as usize);
-        map.resize((w * h) as usize, None);
-        Self { map, w, h }
+        let cells = w.checked_mul(h).expect("map is too big to index");
+        let mut map = Vec::with_capacity(cells as usize);
+        map.resize(cells as usize, None);
+        let spawn = Spawn {
+            x: (w / 2) as i32,
+            y: (h / 2) as i32,
+            radians: 0.0,
+        };
+        Self {
+            map,
+            w,
+            h,
+            spawn,
+            things: Vec::new(),
+            triggers: Vec::new(),
+            markers: Vec::new(),
+            lights: Vec::new(),
+            segments: Vec::new(),
+        }
     }
 
     fn draw_rect(&mut self, x1: u32, y1: u32, x2: u32, y2: u32, material: Option<Wall>) {
@@ -38,6 +172,21 @@ impl Map {
         self.draw_rect(x1, y1, x1 + Self::WALL_THICKNESS, y2, material);
     }
 
+    /// Whether each pixel, `x + y * w`, has a wall in it, counting the
+    /// pixels segments pass over as walls too.
+    pub fn blocked(&self) -> Vec<bool> {
+        let mut blocked: Vec<bool> = self.map.iter().map(Option::is_some).collect();
+        for segment in &self.segments {
+            for (x, y) in segment.points() {
+                // ends on the far edges go in the last column or row
+                let x = (x.max(0.0) as u32).min(self.w - 1);
+                let y = (y.max(0.0) as u32).min(self.h - 1);
+                blocked[(x + y * self.w) as usize] = true;
+            }
+        }
+        blocked
+    }
+
     pub fn into_values(self) -> Vec<Option<Wall>> {
         self.map
     }
@@ -89,6 +238,40 @@ pub fn spooky_map() -> Map {
     map.horiz_wall(256, 384, 384, material);
     map.horiz_wall(416, 480, 384, material);
 
+    map.spawn = Spawn {
+        x: 240,
+        y: 464,
+        radians: 0.5 * std::f32::consts::PI,
+    };
+
+    // a torch by the entrance and a glow off the crystal
+    map.lights.push(Light {
+        x: 240,
+        y: 440,
+        color: image::Rgb([255, 200, 120]),
+        radius: 220.0,
+        falloff: 1.5,
+        flicker: 0.3,
+    });
+    map.lights.push(Light {
+        x: 400,
+        y: 240,
+        color: image::Rgb([160, 200, 255]),
+        radius: 160.0,
+        falloff: 1.0,
+        flicker: 0.0,
+    });
+
+    // an imp pacing the corridor past the entrance
+    map.add_thing(ThingKind::Enemy, "imp", 240, 368);
+    for x in [48, 464] {
+        map.markers.push(Marker {
+            name: "imp".to_string(),
+            x,
+            y: 368,
+        });
+    }
+
     map
 }
 
@@ -0,0 +1,108 @@
+// Things placed in a map besides its walls. Positions are map pixels, the
+// same space as Camera::x and Camera::y.
+use image::Rgb;
+
+use super::Map;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spawn {
+    pub x: i32,
+    pub y: i32,
+    pub radians: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThingKind {
+    Item,
+    Enemy,
+}
+
+/// An item or enemy. `name` says which one, e.g. "key" or "imp"; what that
+/// means is up to the game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thing {
+    pub kind: ThingKind,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A named rectangle, `x1..x2` by `y1..y2`, that fires when entered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trigger {
+    pub name: String,
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
+impl Trigger {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x1..self.x2).contains(&x) && (self.y1..self.y2).contains(&y)
+    }
+}
+
+/// A named point for scripts to refer to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Marker {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A point light. Brightness drops from full at the light to nothing at
+/// `radius`, following `(1 - d / radius) ^ falloff`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub x: i32,
+    pub y: i32,
+    pub color: Rgb<u8>,
+    pub radius: f32,
+    pub falloff: f32,
+    /// How much the light dips when it flickers, from 0.0 for steady to
+    /// 1.0 for going out completely.
+    pub flicker: f32,
+}
+
+impl Light {
+    /// Brightness multiplier at time `t` seconds.
+    pub fn intensity(&self, t: f32) -> f32 {
+        if self.flicker <= 0.0 {
+            return 1.0;
+        }
+        // a few sines at unrelated rates, phased by position so lights
+        // don't flicker in step
+        let phase = (self.x * 7 + self.y * 13) as f32;
+        let wobble = (t * 7.3 + phase).sin() + (t * 13.1 + phase * 0.5).sin() + (t * 23.7).sin();
+        let dip = (wobble / 3.0 + 1.0) / 2.0;
+        1.0 - self.flicker.clamp(0.0, 1.0) * dip
+    }
+}
+
+impl Map {
+    pub fn add_thing(&mut self, kind: ThingKind, name: &str, x: i32, y: i32) {
+        self.things.push(Thing {
+            kind,
+            name: name.to_string(),
+            x,
+            y,
+        });
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &Thing> {
+        self.things.iter().filter(|t| t.kind == ThingKind::Item)
+    }
+
+    pub fn enemies(&self) -> impl Iterator<Item = &Thing> {
+        self.things.iter().filter(|t| t.kind == ThingKind::Enemy)
+    }
+
+    pub fn triggers_at(&self, x: i32, y: i32) -> impl Iterator<Item = &Trigger> {
+        self.triggers.iter().filter(move |t| t.contains(x, y))
+    }
+
+    pub fn marker(&self, name: &str) -> Option<&Marker> {
+        self.markers.iter().find(|m| m.name == name)
+    }
+}
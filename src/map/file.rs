@@ -0,0 +1,420 @@
+// Plain text map files. Metadata lines come first, one per line, then
+// `cells` and one row of characters per map row:
+//
+//     # comment
+//     size 512 512
+//     spawn 240 464 1.5707964
+//     item 100 100 key
+//     enemy 300 200 imp
+//     trigger 32 32 96 96 door
+//     marker 240 464 start
+//     light 240 440 ffc878 220 1.5 0.3
+//     segment 96 96 160 160 b
+//     cells
+//     ssssssss...
+//
+// Lights are x, y, RRGGBB color, radius, falloff and flicker. Segments are
+// thin walls from x1 y1 to x2 y2, then the Wall::symbol they're made of.
+// Cells are '.' for empty and the Wall::symbol of anything else. Names
+// can't contain whitespace.
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use image::Rgb;
+
+use super::{Light, Map, Marker, Segment, Spawn, ThingKind, Trigger, Wall};
+use crate::error::{self, Result};
+
+pub use crate::error::ParseError;
+
+fn error(line: usize, message: impl Into<String>) -> ParseError {
+    ParseError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn args<T: FromStr>(line: usize, words: &[&str], count: usize) -> Result<Vec<T>, ParseError> {
+    if words.len() < count {
+        return Err(error(
+            line,
+            format!(
+                "{} needs {} values",
+                words.first().unwrap_or(&""),
+                count - 1
+            ),
+        ));
+    }
+    words[1..count]
+        .iter()
+        .map(|w| {
+            w.parse()
+                .map_err(|_| error(line, format!("{w:?} isn't a number")))
+        })
+        .collect()
+}
+
+fn name(line: usize, words: &[&str], at: usize) -> Result<String, ParseError> {
+    match words.get(at..) {
+        Some([name]) => Ok(name.to_string()),
+        _ => Err(error(line, format!("{} needs a single name", words[0]))),
+    }
+}
+
+impl Map {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        error::load(path.as_ref())
+    }
+
+    /// Writes the map file, making its directory if need be.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        error::save(path.as_ref(), &self.to_string())
+    }
+}
+
+impl FromStr for Map {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate().map(|(i, l)| (i + 1, l));
+        let mut map: Option<Map> = None;
+        let mut spawn = None;
+        let mut things = Vec::new();
+        let mut triggers = Vec::new();
+        let mut markers = Vec::new();
+        let mut lights = Vec::new();
+        let mut segments = Vec::new();
+
+        for (n, line) in lines.by_ref() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.first().copied() {
+                None => continue,
+                Some(w) if w.starts_with('#') => continue,
+                Some("size") => {
+                    let v: Vec<u32> = args(n, &words, 3)?;
+                    if v[0] == 0 || v[1] == 0 {
+                        return Err(error(n, "size can't be zero"));
+                    }
+                    if v[0] > Map::MAX_SIZE || v[1] > Map::MAX_SIZE {
+                        return Err(error(
+                            n,
+                            format!("size can't be over {0} by {0}", Map::MAX_SIZE),
+                        ));
+                    }
+                    map = Some(Map::new(v[0], v[1]));
+                }
+                Some("spawn") => {
+                    let v: Vec<f32> = args(n, &words, 4)?;
+                    if v.iter().any(|c| !c.is_finite()) {
+                        return Err(error(n, "spawn needs finite numbers"));
+                    }
+                    spawn = Some(Spawn {
+                        x: v[0] as i32,
+                        y: v[1] as i32,
+                        radians: v[2],
+                    });
+                }
+                Some(kind @ ("item" | "enemy")) => {
+                    let v: Vec<i32> = args(n, &words, 3)?;
+                    things.push((
+                        if kind == "item" {
+                            ThingKind::Item
+                        } else {
+                            ThingKind::Enemy
+                        },
+                        name(n, &words, 3)?,
+                        v[0],
+                        v[1],
+                    ));
+                }
+                Some("trigger") => {
+                    let v: Vec<i32> = args(n, &words, 5)?;
+                    triggers.push(Trigger {
+                        name: name(n, &words, 5)?,
+                        x1: v[0],
+                        y1: v[1],
+                        x2: v[2],
+                        y2: v[3],
+                    });
+                }
+                Some("marker") => {
+                    let v: Vec<i32> = args(n, &words, 3)?;
+                    markers.push(Marker {
+                        name: name(n, &words, 3)?,
+                        x: v[0],
+                        y: v[1],
+                    });
+                }
+                Some("light") => {
+                    if words.len() != 7 {
+                        return Err(error(n, "light needs x y color radius falloff flicker"));
+                    }
+                    let color = words[3];
+                    let rgb = u32::from_str_radix(color, 16)
+                        .ok()
+                        .filter(|_| color.len() == 6)
+                        .ok_or_else(|| error(n, format!("{color:?} isn't an RRGGBB color")))?;
+                    let [_, r, g, b] = rgb.to_be_bytes();
+                    let pos: Vec<i32> = args(n, &words, 3)?;
+                    let rest: Vec<f32> = args(n, &words[3..], 4)?;
+                    if rest.iter().any(|v| !v.is_finite()) {
+                        return Err(error(n, "light needs finite numbers"));
+                    }
+                    if rest[0] <= 0.0 {
+                        return Err(error(n, "light radius has to be over 0"));
+                    }
+                    if rest[1] < 0.0 {
+                        return Err(error(n, "light falloff can't be negative"));
+                    }
+                    lights.push(Light {
+                        x: pos[0],
+                        y: pos[1],
+                        color: Rgb([r, g, b]),
+                        radius: rest[0],
+                        falloff: rest[1],
+                        flicker: rest[2],
+                    });
+                }
+                Some("segment") => {
+                    let v: Vec<f32> = args(n, &words, 5)?;
+                    let wall = match words.get(5..) {
+                        Some([symbol]) => {
+                            let mut chars = symbol.chars();
+                            chars
+                                .next()
+                                .filter(|_| chars.next().is_none())
+                                .and_then(Wall::from_symbol)
+                                .ok_or_else(|| error(n, format!("unknown wall {symbol:?}")))?
+                        }
+                        _ => return Err(error(n, "segment needs x1 y1 x2 y2 wall")),
+                    };
+                    if v.iter().any(|c| !c.is_finite()) {
+                        return Err(error(n, "segment needs finite numbers"));
+                    }
+                    segments.push((n, Segment::new((v[0], v[1]), (v[2], v[3]), wall)));
+                }
+                Some("cells") => break,
+                Some(other) => return Err(error(n, format!("unknown entry {other:?}"))),
+            }
+        }
+
+        let Some(mut map) = map else {
+            return Err(error(0, "missing size"));
+        };
+        for y in 0..map.h {
+            let Some((n, row)) = lines.next() else {
+                return Err(error(0, format!("expected {} rows of cells", map.h)));
+            };
+            if row.chars().count() != map.w as usize {
+                return Err(error(n, format!("expected {} cells", map.w)));
+            }
+            for (x, c) in row.chars().enumerate() {
+                let cell = match c {
+                    '.' => None,
+                    c => match Wall::from_symbol(c) {
+                        Some(wall) => Some(wall),
+                        None => return Err(error(n, format!("unknown cell {c:?}"))),
+                    },
+                };
+                map.map[x + (y * map.w) as usize] = cell;
+            }
+        }
+
+        if let Some(spawn) = spawn {
+            map.spawn = spawn;
+        }
+        for (kind, name, x, y) in things {
+            map.add_thing(kind, &name, x, y);
+        }
+        map.triggers = triggers;
+        map.markers = markers;
+        map.lights = lights;
+        // ends can sit on the far edges, but no further out
+        let (w, h) = (map.w as f32, map.h as f32);
+        for (n, segment) in segments {
+            for (x, y) in [segment.from, segment.to] {
+                if !(0.0..=w).contains(&x) || !(0.0..=h).contains(&y) {
+                    return Err(error(
+                        n,
+                        format!("segment end ({x}, {y}) is outside the {w}x{h} map"),
+                    ));
+                }
+            }
+            map.segments.push(segment);
+        }
+        Ok(map)
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "size {} {}", self.w, self.h)?;
+        let spawn = self.spawn;
+        writeln!(f, "spawn {} {} {}", spawn.x, spawn.y, spawn.radians)?;
+        for thing in &self.things {
+            let kind = match thing.kind {
+                ThingKind::Item => "item",
+                ThingKind::Enemy => "enemy",
+            };
+            writeln!(f, "{kind} {} {} {}", thing.x, thing.y, thing.name)?;
+        }
+        for t in &self.triggers {
+            writeln!(f, "trigger {} {} {} {} {}", t.x1, t.y1, t.x2, t.y2, t.name)?;
+        }
+        for marker in &self.markers {
+            writeln!(f, "marker {} {} {}", marker.x, marker.y, marker.name)?;
+        }
+        for l in &self.lights {
+            let [r, g, b] = l.color.0;
+            writeln!(
+                f,
+                "light {} {} {r:02x}{g:02x}{b:02x} {} {} {}",
+                l.x, l.y, l.radius, l.falloff, l.flicker
+            )?;
+        }
+        for s in &self.segments {
+            let ((x1, y1), (x2, y2)) = (s.from, s.to);
+            writeln!(f, "segment {x1} {y1} {x2} {y2} {}", s.wall.symbol())?;
+        }
+        writeln!(f, "cells")?;
+        for row in self.map.chunks(self.w as usize) {
+            let row: String = row
+                .iter()
+                .map(|cell| cell.map_or('.', Wall::symbol))
+                .collect();
+            writeln!(f, "{row}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map;
+
+    #[test]
+    fn round_trip() {
+        let mut map = map::spooky_map();
+        map.add_thing(ThingKind::Item, "key", 100, 100);
+        map.add_thing(ThingKind::Enemy, "imp", 300, 200);
+        map.triggers.push(Trigger {
+            name: "door".to_string(),
+            x1: 32,
+            y1: 32,
+            x2: 96,
+            y2: 96,
+        });
+        map.markers.push(Marker {
+            name: "start".to_string(),
+            x: 240,
+            y: 464,
+        });
+        map.segments
+            .push(Segment::new((96.0, 96.0), (160.5, 160.0), Wall::Brick));
+
+        let text = map.to_string();
+        let loaded: Map = text.parse().unwrap();
+        assert!(loaded.map == map.map);
+        assert_eq!(loaded.spawn, map.spawn);
+        assert_eq!(loaded.things, map.things);
+        assert_eq!(loaded.triggers, map.triggers);
+        assert_eq!(loaded.markers, map.markers);
+        assert_eq!(loaded.lights, map.lights);
+        assert_eq!(loaded.lights.len(), 2);
+        assert_eq!(loaded.segments, map.segments);
+        assert_eq!(loaded.to_string(), text);
+    }
+
+    #[test]
+    fn metadata_api() {
+        let map: Map = "# tiny\nsize 3 2\nspawn 1 1 0\nitem 1 0 key\nenemy 2 1 imp\n\
+                        trigger 0 0 2 2 hall\nmarker 2 0 exit\ncells\ns.b\n.dc\n"
+            .parse()
+            .unwrap();
+        assert_eq!(map.map[2], Some(Wall::Brick));
+        assert_eq!(map.map[4], Some(Wall::Dirt));
+        assert_eq!(
+            map.items().map(|t| t.name.as_str()).collect::<Vec<_>>(),
+            ["key"]
+        );
+        assert_eq!(map.enemies().count(), 1);
+        assert_eq!(map.triggers_at(1, 1).count(), 1);
+        assert_eq!(map.triggers_at(2, 1).count(), 0);
+        assert_eq!(map.marker("exit").map(|m| (m.x, m.y)), Some((2, 0)));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "size 2\ncells\n".parse::<Map>().err(),
+            Some(error(1, "size needs 2 values"))
+        );
+        assert_eq!(
+            "size 70000 70000\ncells\n".parse::<Map>().err(),
+            Some(error(1, "size can't be over 8192 by 8192"))
+        );
+        assert_eq!(
+            "cells\n".parse::<Map>().err(),
+            Some(error(0, "missing size"))
+        );
+        assert_eq!(
+            "size 2 1\nspawn 1 x 0\ncells\n..\n".parse::<Map>().err(),
+            Some(error(2, "\"x\" isn't a number"))
+        );
+        assert_eq!(
+            "size 2 1\nspawn 1 0 nan\ncells\n..\n".parse::<Map>().err(),
+            Some(error(2, "spawn needs finite numbers"))
+        );
+        assert_eq!(
+            "size 2 1\ncells\n.q\n".parse::<Map>().err(),
+            Some(error(3, "unknown cell 'q'"))
+        );
+        assert_eq!(
+            "size 2 1\nlight 1 0 fffff 10 1 0\ncells\n..\n"
+                .parse::<Map>()
+                .err(),
+            Some(error(2, "\"fffff\" isn't an RRGGBB color"))
+        );
+        let light = |rest: &str| {
+            format!("size 2 1\nlight 1 0 ffffff {rest}\ncells\n..\n")
+                .parse::<Map>()
+                .err()
+        };
+        assert_eq!(
+            light("nan 1 0"),
+            Some(error(2, "light needs finite numbers"))
+        );
+        assert_eq!(
+            light("0 1 0"),
+            Some(error(2, "light radius has to be over 0"))
+        );
+        assert_eq!(
+            light("10 -1 0"),
+            Some(error(2, "light falloff can't be negative"))
+        );
+        assert_eq!(
+            "size 2 1\nsegment 0 0 1 1 q\ncells\n..\n"
+                .parse::<Map>()
+                .err(),
+            Some(error(2, "unknown wall \"q\""))
+        );
+        assert_eq!(
+            "size 2 1\nsegment 0 0 inf 1 b\ncells\n..\n"
+                .parse::<Map>()
+                .err(),
+            Some(error(2, "segment needs finite numbers"))
+        );
+        assert_eq!(
+            "size 2 1\nsegment 0 0 2 1 b\nsegment 0 0 3 1 b\ncells\n..\n"
+                .parse::<Map>()
+                .err(),
+            Some(error(3, "segment end (3, 1) is outside the 2x1 map"))
+        );
+        assert_eq!(
+            "size 2 2\ncells\n..\n".parse::<Map>().err(),
+            Some(error(0, "expected 2 rows of cells"))
+        );
+    }
+}
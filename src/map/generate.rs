@@ -0,0 +1,487 @@
+// Seeded map generators. Everything is laid out on a coarse grid of cells
+// that are Map::WALL_THICKNESS pixels across, then stamped into a Map, so a
+// generator called with (16, 16) gives the same 512x512 map size as
+// spooky_map. The same seed always gives the same map.
+use super::{Map, Wall};
+
+// a table of its own rather than Wall::ALL, so new materials don't change
+// what old seeds make. the last slot was crystal until it turned
+// see-through, and generated walls should all be solid
+const MATERIALS: [Wall; 4] = [Wall::Dirt, Wall::Brick, Wall::Stone, Wall::Stone];
+
+struct Rng(u64);
+
+impl Rng {
+    // splitmix64: tiny, and stable forever, unlike pulling in a crate whose
+    // output could change between versions
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        (self.next() % n as u64) as u32
+    }
+
+    fn range(&mut self, lo: u32, hi: u32) -> u32 {
+        // lo inclusive, hi exclusive
+        lo + self.below(hi - lo)
+    }
+
+    fn chance(&mut self, percent: u32) -> bool {
+        self.below(100) < percent
+    }
+
+    fn material(&mut self) -> Wall {
+        MATERIALS[self.below(MATERIALS.len() as u32) as usize]
+    }
+}
+
+struct Cells {
+    solid: Vec<bool>,
+    w: u32,
+    h: u32,
+}
+
+impl Cells {
+    fn new(w: u32, h: u32) -> Self {
+        Self {
+            solid: vec![true; (w * h) as usize],
+            w,
+            h,
+        }
+    }
+
+    fn is_solid(&self, x: u32, y: u32) -> bool {
+        self.solid[(x + y * self.w) as usize]
+    }
+
+    fn set(&mut self, x: u32, y: u32, solid: bool) {
+        self.solid[(x + y * self.w) as usize] = solid;
+    }
+
+    fn on_border(&self, x: u32, y: u32) -> bool {
+        x == 0 || y == 0 || x == self.w - 1 || y == self.h - 1
+    }
+
+    fn open_neighbors(&self, x: u32, y: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let candidates = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        candidates
+            .into_iter()
+            .filter(|&(x, y)| x < self.w && y < self.h && !self.is_solid(x, y))
+    }
+
+    fn regions(&self) -> Vec<Vec<(u32, u32)>> {
+        // 4-connected groups of open cells
+        let mut seen = vec![false; self.solid.len()];
+        let mut regions = Vec::new();
+        for y in 0..self.h {
+            for x in 0..self.w {
+                let idx = (x + y * self.w) as usize;
+                if self.solid[idx] || seen[idx] {
+                    continue;
+                }
+                seen[idx] = true;
+                let mut region = vec![(x, y)];
+                let mut next = 0;
+                while next < region.len() {
+                    let (cx, cy) = region[next];
+                    next += 1;
+                    for (nx, ny) in self.open_neighbors(cx, cy) {
+                        let nidx = (nx + ny * self.w) as usize;
+                        if !seen[nidx] {
+                            seen[nidx] = true;
+                            region.push((nx, ny));
+                        }
+                    }
+                }
+                regions.push(region);
+            }
+        }
+        regions
+    }
+
+    fn is_connected(&self) -> bool {
+        self.regions().len() == 1
+    }
+
+    fn into_map(self, sites: &[(u32, u32, Wall)]) -> Map {
+        // every solid cell takes the material of the nearest site, apart
+        // from the outer boundary which is always stone
+        let scale = Map::WALL_THICKNESS;
+        let mut map = Map::new(self.w * scale, self.h * scale);
+        // spawn in the middle of the first open cell
+        if let Some(idx) = self.solid.iter().position(|solid| !solid) {
+            let (x, y) = (idx as u32 % self.w, idx as u32 / self.w);
+            map.spawn.x = (x * scale + scale / 2) as i32;
+            map.spawn.y = (y * scale + scale / 2) as i32;
+        }
+        for y in 0..self.h {
+            for x in 0..self.w {
+                if !self.is_solid(x, y) {
+                    continue;
+                }
+                let material = if self.on_border(x, y) {
+                    Wall::Stone
+                } else {
+                    nearest_site(sites, x, y)
+                };
+                map.draw_rect(
+                    x * scale,
+                    y * scale,
+                    (x + 1) * scale,
+                    (y + 1) * scale,
+                    Some(material),
+                );
+            }
+        }
+        map
+    }
+}
+
+fn nearest_site(sites: &[(u32, u32, Wall)], x: u32, y: u32) -> Wall {
+    sites
+        .iter()
+        .min_by_key(|&&(sx, sy, _)| {
+            let dx = sx as i64 - x as i64;
+            let dy = sy as i64 - y as i64;
+            dx * dx + dy * dy
+        })
+        .map_or(Wall::Stone, |&(_, _, material)| material)
+}
+
+fn scatter_sites(rng: &mut Rng, w: u32, h: u32) -> Vec<(u32, u32, Wall)> {
+    let count = std::cmp::max(4, w * h / 64);
+    (0..count)
+        .map(|_| (rng.below(w), rng.below(h), rng.material()))
+        .collect()
+}
+
+/// Recursive backtracker maze, one cell wide corridors. Even dimensions
+/// leave an extra row or column of wall along the far edge.
+pub fn maze(cols: u32, rows: u32, seed: u64) -> Map {
+    assert!(cols >= 3 && rows >= 3, "maze needs at least 3x3 cells");
+    let mut rng = Rng(seed);
+    let mut cells = Cells::new(cols, rows);
+
+    cells.set(1, 1, false);
+    let mut stack = vec![(1, 1)];
+    while let Some(&(x, y)) = stack.last() {
+        let mut options = Vec::with_capacity(4);
+        if x >= 3 {
+            options.push((x - 2, y));
+        }
+        if y >= 3 {
+            options.push((x, y - 2));
+        }
+        if x + 2 < cols - 1 {
+            options.push((x + 2, y));
+        }
+        if y + 2 < rows - 1 {
+            options.push((x, y + 2));
+        }
+        options.retain(|&(nx, ny)| cells.is_solid(nx, ny));
+        if options.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (nx, ny) = options[rng.below(options.len() as u32) as usize];
+        cells.set((x + nx) / 2, (y + ny) / 2, false);
+        cells.set(nx, ny, false);
+        stack.push((nx, ny));
+    }
+
+    debug_assert!(cells.is_connected());
+    let sites = scatter_sites(&mut rng, cols, rows);
+    cells.into_map(&sites)
+}
+
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+const MIN_LEAF: u32 = 6;
+
+fn carve_corridor(cells: &mut Cells, from: (u32, u32), to: (u32, u32), rng: &mut Rng) {
+    // L-shaped, turning at a random corner
+    let corner = if rng.chance(50) {
+        (to.0, from.1)
+    } else {
+        (from.0, to.1)
+    };
+    for (a, b) in [(from, corner), (corner, to)] {
+        for y in a.1.min(b.1)..=a.1.max(b.1) {
+            for x in a.0.min(b.0)..=a.0.max(b.0) {
+                cells.set(x, y, false);
+            }
+        }
+    }
+}
+
+fn split(
+    rect: Rect,
+    cells: &mut Cells,
+    rng: &mut Rng,
+    sites: &mut Vec<(u32, u32, Wall)>,
+) -> (u32, u32) {
+    // returns the centre of one room in this subtree so the caller can
+    // join it to its sibling
+    let can_split_x = rect.w >= 2 * MIN_LEAF;
+    let can_split_y = rect.h >= 2 * MIN_LEAF;
+    if !can_split_x && !can_split_y {
+        // leaf: a room with at least one cell of wall around it
+        let w = rng.range(2, rect.w - 1);
+        let h = rng.range(2, rect.h - 1);
+        let x = rect.x + rng.range(1, rect.w - w);
+        let y = rect.y + rng.range(1, rect.h - h);
+        for ry in y..y + h {
+            for rx in x..x + w {
+                cells.set(rx, ry, false);
+            }
+        }
+        let centre = (x + w / 2, y + h / 2);
+        sites.push((centre.0, centre.1, rng.material()));
+        return centre;
+    }
+    let vertical = if can_split_x && can_split_y {
+        rng.chance(50)
+    } else {
+        can_split_x
+    };
+    let (a, b) = if vertical {
+        let at = rng.range(MIN_LEAF, rect.w - MIN_LEAF + 1);
+        (
+            Rect { w: at, ..rect },
+            Rect {
+                x: rect.x + at,
+                w: rect.w - at,
+                ..rect
+            },
+        )
+    } else {
+        let at = rng.range(MIN_LEAF, rect.h - MIN_LEAF + 1);
+        (
+            Rect { h: at, ..rect },
+            Rect {
+                y: rect.y + at,
+                h: rect.h - at,
+                ..rect
+            },
+        )
+    };
+    let from = split(a, cells, rng, sites);
+    let to = split(b, cells, rng, sites);
+    carve_corridor(cells, from, to, rng);
+    from
+}
+
+/// Rooms and corridors from binary space partitioning. Each room picks a
+/// material for the walls around it.
+pub fn dungeon(cols: u32, rows: u32, seed: u64) -> Map {
+    assert!(
+        cols >= MIN_LEAF && rows >= MIN_LEAF,
+        "dungeon needs at least {MIN_LEAF}x{MIN_LEAF} cells"
+    );
+    let mut rng = Rng(seed);
+    let mut cells = Cells::new(cols, rows);
+    let mut sites = Vec::new();
+    // keep the outer ring solid by partitioning only the interior
+    let inner = Rect {
+        x: 1,
+        y: 1,
+        w: cols - 2,
+        h: rows - 2,
+    };
+    split(inner, &mut cells, &mut rng, &mut sites);
+
+    debug_assert!(cells.is_connected());
+    cells.into_map(&sites)
+}
+
+/// Cellular automata caves. Pockets that end up cut off from the biggest
+/// cave are filled in, so every open cell can be reached.
+pub fn caves(cols: u32, rows: u32, seed: u64) -> Map {
+    assert!(cols >= 3 && rows >= 3, "caves need at least 3x3 cells");
+    let mut rng = Rng(seed);
+    let mut cells = Cells::new(cols, rows);
+    for y in 1..rows - 1 {
+        for x in 1..cols - 1 {
+            cells.set(x, y, rng.chance(45));
+        }
+    }
+
+    for _ in 0..4 {
+        let mut next = Cells::new(cols, rows);
+        for y in 1..rows - 1 {
+            for x in 1..cols - 1 {
+                let mut walls = 0;
+                for ny in y - 1..=y + 1 {
+                    for nx in x - 1..=x + 1 {
+                        if (nx, ny) != (x, y) && cells.is_solid(nx, ny) {
+                            walls += 1;
+                        }
+                    }
+                }
+                let solid = match walls {
+                    0..=3 => false,
+                    4 => cells.is_solid(x, y),
+                    _ => true,
+                };
+                next.set(x, y, solid);
+            }
+        }
+        cells = next;
+    }
+
+    let mut regions = cells.regions();
+    regions.sort_by_key(|region| std::cmp::Reverse(region.len()));
+    for region in regions.iter().skip(1) {
+        for &(x, y) in region {
+            cells.set(x, y, true);
+        }
+    }
+
+    debug_assert!(regions.is_empty() || cells.is_connected());
+    let sites = scatter_sites(&mut rng, cols, rows);
+    cells.into_map(&sites)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells_of(map: &Map) -> Cells {
+        let scale = Map::WALL_THICKNESS;
+        let (w, h) = (map.w / scale, map.h / scale);
+        let mut cells = Cells::new(w, h);
+        for y in 0..h {
+            for x in 0..w {
+                let idx = (x * scale + y * scale * map.w) as usize;
+                cells.set(x, y, map.map[idx].is_some());
+            }
+        }
+        cells
+    }
+
+    fn check(generate: fn(u32, u32, u64) -> Map) {
+        for seed in 0..20 {
+            let map = generate(24, 17, seed);
+            assert_eq!((map.w, map.h), (24 * 32, 17 * 32));
+            let cells = cells_of(&map);
+            assert!(cells.is_connected(), "seed {seed} isn't connected");
+            for y in 0..cells.h {
+                for x in 0..cells.w {
+                    if cells.on_border(x, y) {
+                        assert!(cells.is_solid(x, y), "seed {seed} leaks at {x},{y}");
+                    }
+                }
+            }
+        }
+    }
+
+    fn deterministic(generate: fn(u32, u32, u64) -> Map) {
+        assert!(generate(20, 20, 7).map == generate(20, 20, 7).map);
+        assert!(generate(20, 20, 7).map != generate(20, 20, 8).map);
+    }
+
+    #[test]
+    fn maze_is_valid() {
+        check(maze);
+        deterministic(maze);
+    }
+
+    #[test]
+    fn dungeon_is_valid() {
+        check(dungeon);
+        deterministic(dungeon);
+    }
+
+    #[test]
+    fn caves_are_valid() {
+        check(caves);
+        deterministic(caves);
+    }
+
+    #[test]
+    fn golden_seeds() {
+        // each cell's material for a small map of each kind, so a change
+        // to what a seed makes can't go unnoticed
+        let symbols = |map: Map| -> Vec<String> {
+            let scale = Map::WALL_THICKNESS as usize;
+            map.map
+                .chunks(map.w as usize)
+                .step_by(scale)
+                .map(|row| {
+                    row.iter()
+                        .step_by(scale)
+                        .map(|c| c.map_or('.', Wall::symbol))
+                        .collect()
+                })
+                .collect()
+        };
+        assert_eq!(
+            symbols(maze(13, 9, 3)),
+            [
+                "sssssssssssss",
+                "s.s.....s...s",
+                "s.s.s.s.sss.s",
+                "s.s.s.s.....s",
+                "s.bbs.sssss.s",
+                "s.b...s...s.s",
+                "s.b.bbb.s.s.s",
+                "s...b...b...s",
+                "sssssssssssss",
+            ]
+        );
+        assert_eq!(
+            symbols(dungeon(16, 10, 5)),
+            [
+                "ssssssssssssssss",
+                "sdddddddssssssss",
+                "sdddddddsss..sss",
+                "sd...........sss",
+                "sd......dsssssss",
+                "sd......dsssssss",
+                "sd......ddssssss",
+                "sd......ddssssss",
+                "sdddddddddssssss",
+                "ssssssssssssssss",
+            ]
+        );
+        assert_eq!(
+            symbols(caves(16, 10, 5)),
+            [
+                "ssssssssssssssss",
+                "sssssddddd..bbbs",
+                "ssssddddd....bbs",
+                "sss...........bs",
+                "ss............bs",
+                "ssd...........bs",
+                "sddd..........bs",
+                "sddddd.......bbs",
+                "sddddddddddbbbbs",
+                "ssssssssssssssss",
+            ]
+        );
+    }
+
+    #[test]
+    fn maze_is_perfect() {
+        // a spanning tree over the odd cells: one more open cell than
+        // there are passages between them
+        let cells = cells_of(&maze(15, 15, 3));
+        let open = cells.solid.iter().filter(|solid| !**solid).count();
+        assert_eq!(open, 7 * 7 + (7 * 7 - 1));
+    }
+}
@@ -0,0 +1,58 @@
+// Walls with no thickness: straight lines between two points rather than
+// cells, for thin partitions and for diagonals that cells could only
+// stair-step. Points are map pixels, and a wall along a grid line sits on
+// the boundary between two rows or columns of cells.
+use super::Wall;
+use crate::cast::Side;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment<T = Wall> {
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    pub wall: T,
+}
+
+impl<T> Segment<T> {
+    pub fn new(from: (f32, f32), to: (f32, f32), wall: T) -> Self {
+        Self { from, to, wall }
+    }
+
+    /// A thin wall along the line y = `y`, from x1 to x2.
+    pub fn horizontal(x1: f32, x2: f32, y: f32, wall: T) -> Self {
+        Self::new((x1, y), (x2, y), wall)
+    }
+
+    /// A thin wall along the line x = `x`, from y1 to y2.
+    pub fn vertical(y1: f32, y2: f32, x: f32, wall: T) -> Self {
+        Self::new((x, y1), (x, y2), wall)
+    }
+
+    /// Which kind of face it shows, going by whichever way it mostly runs,
+    /// so diagonals get shaded like the nearer of the two.
+    pub fn side(&self) -> Side {
+        let (dx, dy) = (self.to.0 - self.from.0, self.to.1 - self.from.1);
+        if dy.abs() > dx.abs() {
+            Side::X
+        } else {
+            Side::Y
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.to.0 - self.from.0).hypot(self.to.1 - self.from.1)
+    }
+
+    /// Points no more than a pixel apart each way from one end to the
+    /// other, so the pixels under them join up at least corner to corner.
+    pub fn points(&self) -> impl Iterator<Item = (f32, f32)> {
+        let (from, (dx, dy)) = (
+            self.from,
+            (self.to.0 - self.from.0, self.to.1 - self.from.1),
+        );
+        let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as u32;
+        (0..=steps).map(move |i| {
+            let t = i as f32 / steps as f32;
+            (from.0 + dx * t, from.1 + dy * t)
+        })
+    }
+}
@@ -0,0 +1,294 @@
+use std::fmt;
+
+use super::{Map, Wall};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// A run of open pixels on the outer edge, from `start` to `end`
+    /// inclusive. Rays and the camera can walk out of the map here.
+    Leak {
+        start: (u32, u32),
+        end: (u32, u32),
+    },
+    SpawnOutOfBounds {
+        x: i32,
+        y: i32,
+    },
+    SpawnInWall {
+        x: u32,
+        y: u32,
+        wall: Wall,
+    },
+    /// An open area the spawn can't reach. `at` is its top-left-most pixel.
+    Unreachable {
+        at: (u32, u32),
+        cells: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Leak { start, end } => write!(
+                f,
+                "boundary leak from ({}, {}) to ({}, {})",
+                start.0, start.1, end.0, end.1
+            ),
+            Problem::SpawnOutOfBounds { x, y } => {
+                write!(f, "spawn ({x}, {y}) is outside the map")
+            }
+            Problem::SpawnInWall { x, y, wall } => {
+                write!(f, "spawn ({x}, {y}) is inside a {wall:?} wall")
+            }
+            Problem::Unreachable { at, cells } => write!(
+                f,
+                "{cells} open cells around ({}, {}) can't be reached from spawn",
+                at.0, at.1
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+    pub open: usize,
+    /// Cell count for each of `Wall::ALL`, in that order.
+    pub materials: [(Wall, usize); Wall::ALL.len()],
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "open: {}", self.open)?;
+        for (wall, count) in self.materials {
+            write!(f, ", {wall:?}: {count}")?;
+        }
+        for problem in &self.problems {
+            write!(f, "\n{problem}")?;
+        }
+        Ok(())
+    }
+}
+
+fn leaks(map: &Map, blocked: &[bool]) -> Vec<Problem> {
+    // walk the edge clockwise, collecting runs of open pixels
+    let (w, h) = (map.w, map.h);
+    let mut edge = Vec::with_capacity(2 * (w + h) as usize);
+    edge.extend((0..w).map(|x| (x, 0)));
+    edge.extend((1..h).map(|y| (w - 1, y)));
+    edge.extend((0..w - 1).rev().map(|x| (x, h - 1)));
+    edge.extend((1..h - 1).rev().map(|y| (0, y)));
+
+    let mut problems = Vec::new();
+    let mut run: Option<((u32, u32), (u32, u32))> = None;
+    for (x, y) in edge {
+        let open = !blocked[(x + y * w) as usize];
+        run = match (run, open) {
+            (None, true) => Some(((x, y), (x, y))),
+            (Some((start, _)), true) => Some((start, (x, y))),
+            (Some((start, end)), false) => {
+                problems.push(Problem::Leak { start, end });
+                None
+            }
+            (None, false) => None,
+        };
+    }
+    if let Some((start, end)) = run {
+        problems.push(Problem::Leak { start, end });
+    }
+    problems
+}
+
+fn regions(map: &Map, blocked: &[bool]) -> Vec<Vec<u32>> {
+    // 4-connected groups of open pixels, as indices into map.map
+    let mut seen = blocked.to_vec();
+    let mut regions = Vec::new();
+    for start in 0..map.map.len() {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut region = vec![start as u32];
+        let mut next = 0;
+        while next < region.len() {
+            let idx = region[next];
+            next += 1;
+            let (x, y) = (idx % map.w, idx / map.w);
+            let mut neighbors = Vec::with_capacity(4);
+            if x > 0 {
+                neighbors.push(idx - 1);
+            }
+            if x + 1 < map.w {
+                neighbors.push(idx + 1);
+            }
+            if y > 0 {
+                neighbors.push(idx - map.w);
+            }
+            if y + 1 < map.h {
+                neighbors.push(idx + map.w);
+            }
+            for n in neighbors {
+                if !seen[n as usize] {
+                    seen[n as usize] = true;
+                    region.push(n);
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+impl Map {
+    /// Checks that the map is closed and that everything open can be
+    /// reached from the spawn. Segments close off space like walls do.
+    pub fn validate(&self) -> Report {
+        let blocked = self.blocked();
+        let mut problems = leaks(self, &blocked);
+
+        let (x, y) = (self.spawn.x, self.spawn.y);
+        let spawn_idx = if x < 0 || y < 0 || x as u32 >= self.w || y as u32 >= self.h {
+            problems.push(Problem::SpawnOutOfBounds { x, y });
+            None
+        } else {
+            let (x, y) = (x as u32, y as u32);
+            let idx = x + y * self.w;
+            match self.map[idx as usize] {
+                Some(wall) => {
+                    problems.push(Problem::SpawnInWall { x, y, wall });
+                    None
+                }
+                None => Some(idx),
+            }
+        };
+
+        let regions = regions(self, &blocked);
+        // without a usable spawn, measure against the biggest open area.
+        // standing on a segment is fine, but it's in no area
+        let home = match spawn_idx.filter(|&idx| !blocked[idx as usize]) {
+            Some(idx) => regions.iter().position(|r| r.contains(&idx)),
+            None => (0..regions.len()).max_by_key(|&i| regions[i].len()),
+        };
+        for (i, region) in regions.iter().enumerate() {
+            if Some(i) != home {
+                let first = region[0];
+                problems.push(Problem::Unreachable {
+                    at: (first % self.w, first / self.w),
+                    cells: region.len(),
+                });
+            }
+        }
+
+        let mut materials = Wall::ALL.map(|wall| (wall, 0));
+        let mut open = 0;
+        for cell in &self.map {
+            match cell {
+                Some(wall) => materials[*wall as usize].1 += 1,
+                None => open += 1,
+            }
+        }
+
+        Report {
+            problems,
+            open,
+            materials,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{self, Segment};
+
+    #[test]
+    fn spooky_map_is_closed() {
+        let report = map::spooky_map().validate();
+        assert!(!report
+            .problems
+            .iter()
+            .any(|p| matches!(p, Problem::Leak { .. })));
+        assert_eq!(
+            report.open + report.materials.iter().map(|m| m.1).sum::<usize>(),
+            512 * 512
+        );
+    }
+
+    #[test]
+    fn finds_problems() {
+        let mut map = Map::new(64, 64);
+        map.draw_rect(0, 0, 64, 4, Some(Wall::Stone));
+        map.draw_rect(0, 60, 64, 64, Some(Wall::Stone));
+        map.draw_rect(0, 0, 4, 64, Some(Wall::Stone));
+        map.draw_rect(60, 0, 64, 64, Some(Wall::Brick));
+        // a gap in the right wall and a sealed box in the middle
+        map.draw_rect(60, 10, 64, 20, None);
+        map.draw_rect(30, 30, 40, 40, Some(Wall::Dirt));
+        map.draw_rect(33, 33, 37, 37, None);
+
+        (map.spawn.x, map.spawn.y) = (10, 10);
+        let report = map.validate();
+        assert_eq!(
+            report.problems,
+            vec![
+                Problem::Leak {
+                    start: (63, 10),
+                    end: (63, 19)
+                },
+                Problem::Unreachable {
+                    at: (33, 33),
+                    cells: 16
+                },
+            ]
+        );
+
+        // a segment along the edge plugs the gap, and one across the
+        // room shuts off the bottom of it
+        map.segments
+            .push(Segment::vertical(10.0, 20.0, 64.0, Wall::Brick));
+        map.segments
+            .push(Segment::horizontal(4.0, 60.0, 50.0, Wall::Crystal));
+        assert_eq!(
+            map.validate().problems,
+            vec![
+                Problem::Unreachable {
+                    at: (33, 33),
+                    cells: 16
+                },
+                Problem::Unreachable {
+                    at: (4, 51),
+                    cells: 504
+                },
+            ]
+        );
+        map.segments.clear();
+
+        (map.spawn.x, map.spawn.y) = (31, 31);
+        let report = map.validate();
+        assert!(report.problems.contains(&Problem::SpawnInWall {
+            x: 31,
+            y: 31,
+            wall: Wall::Dirt
+        }));
+        (map.spawn.x, map.spawn.y) = (-1, 5);
+        let report = map.validate();
+        assert!(report
+            .problems
+            .contains(&Problem::SpawnOutOfBounds { x: -1, y: 5 }));
+    }
+
+    #[test]
+    fn generated_maps_validate() {
+        for seed in 0..5 {
+            assert!(map::generate::maze(15, 15, seed).validate().is_ok());
+            assert!(map::generate::dungeon(24, 24, seed).validate().is_ok());
+            assert!(map::generate::caves(24, 24, seed).validate().is_ok());
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use image::Rgb;
+
+use crate::error::{self, Result};
+use crate::frame::Frame;
+
+pub use crate::error::ParseError;
+
+/// Rust Gold 8, the palette everything is drawn in by default.
+/// https://lospec.com/palette-list/rust-gold-8
+pub const RUST_GOLD_8: [Rgb<u8>; 8] = [
+    Rgb([246, 205, 38]), // Gold
+    Rgb([172, 107, 38]), // Orange
+    Rgb([86, 50, 38]),   // Rust
+    Rgb([51, 28, 23]),   // Maroon
+    Rgb([187, 127, 87]), // Creamsicle
+    Rgb([114, 89, 86]),  // Purple
+    Rgb([57, 57, 57]),   // Gray
+    Rgb([32, 32, 32]),   // Black
+];
+
+/// An indexed framebuffer: each pixel is an index into a `Palette`.
+pub type IndexedImage = image::GrayImage;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: Vec<Rgb<u8>>,
+}
+
+fn error(line: usize, message: impl Into<String>) -> ParseError {
+    ParseError {
+        line,
+        message: message.into(),
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: RUST_GOLD_8.to_vec(),
+        }
+    }
+}
+
+impl Palette {
+    /// Reads a palette file, in either format `from_str` takes.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        error::load(path.as_ref())
+    }
+
+    /// The color at `idx`. Indices past the end wrap around, so art drawn
+    /// for a bigger palette still shows up in a smaller one.
+    pub fn get(&self, idx: u8) -> Rgb<u8> {
+        self.colors[idx as usize % self.colors.len()]
+    }
+
+    /// Lospec's .hex format: one RRGGBB per line.
+    pub fn parse_hex(s: &str) -> Result<Self, ParseError> {
+        let mut colors = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim().trim_start_matches('#');
+            if line.is_empty() {
+                continue;
+            }
+            let value = u32::from_str_radix(line, 16)
+                .ok()
+                .filter(|_| line.len() == 6)
+                .ok_or_else(|| error(i + 1, format!("{line:?} isn't an RRGGBB color")))?;
+            let [_, r, g, b] = value.to_be_bytes();
+            colors.push(Rgb([r, g, b]));
+        }
+        Self::from_colors(colors)
+    }
+
+    /// GIMP's .gpl format: a "GIMP Palette" header, then "R G B name" lines.
+    pub fn parse_gpl(s: &str) -> Result<Self, ParseError> {
+        let mut lines = s.lines().enumerate();
+        match lines.next() {
+            Some((_, header)) if header.trim() == "GIMP Palette" => {}
+            _ => return Err(error(1, "missing \"GIMP Palette\" header")),
+        }
+        let mut colors = Vec::new();
+        for (i, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.contains(':') {
+                // comments and Name:/Columns: headers
+                continue;
+            }
+            let channels: Vec<u8> = line
+                .split_whitespace()
+                .take(3)
+                .map(|c| c.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| error(i + 1, format!("{line:?} isn't an R G B color")))?;
+            match channels[..] {
+                [r, g, b] => colors.push(Rgb([r, g, b])),
+                _ => return Err(error(i + 1, format!("{line:?} isn't an R G B color"))),
+            }
+        }
+        Self::from_colors(colors)
+    }
+
+    fn from_colors(colors: Vec<Rgb<u8>>) -> Result<Self, ParseError> {
+        match colors.len() {
+            0 => Err(error(0, "palette has no colors")),
+            1..=256 => Ok(Self { colors }),
+            n => Err(error(0, format!("palette has {n} colors, at most 256 fit"))),
+        }
+    }
+
+    /// Blends every entry towards `other`'s entry at the same index; `t`
+    /// of 0.0 is this palette, 1.0 is `other`. Good for day/night.
+    pub fn lerp(&self, other: &Palette, t: f32) -> Palette {
+        let colors = (0..self.colors.len())
+            .map(|i| mix(self.colors[i], other.get(i as u8), t))
+            .collect();
+        Palette { colors }
+    }
+
+    /// Every entry pulled `t` of the way towards one color, like a red
+    /// flash when the player takes damage.
+    pub fn tinted(&self, color: Rgb<u8>, t: f32) -> Palette {
+        let colors = self.colors.iter().map(|&c| mix(c, color, t)).collect();
+        Palette { colors }
+    }
+
+    /// Resolves an indexed frame into `img`, which must be the same size.
+    pub fn apply<F: Frame>(&self, indexed: &IndexedImage, img: &mut F) {
+        let pixels = img.channels_mut().chunks_exact_mut(F::CHANNELS);
+        for (src, dst) in indexed.pixels().zip(pixels) {
+            F::encode(self.get(src.0[0]), dst);
+        }
+    }
+}
+
+impl FromStr for Palette {
+    type Err = ParseError;
+
+    /// Either format, told apart by the GPL header.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim_start().starts_with("GIMP Palette") {
+            Self::parse_gpl(s)
+        } else {
+            Self::parse_hex(s)
+        }
+    }
+}
+
+fn mix(a: Rgb<u8>, b: Rgb<u8>, t: f32) -> Rgb<u8> {
+    let t = t.clamp(0.0, 1.0);
+    Rgb([0, 1, 2].map(|i| (a[i] as f32 + (b[i] as f32 - a[i] as f32) * t).round() as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        let palette: Palette = "f6cd26\n#ac6b26\n\n563226\n".parse().unwrap();
+        assert_eq!(palette.colors, RUST_GOLD_8[..3]);
+        assert_eq!(palette.get(4), RUST_GOLD_8[1]);
+        assert_eq!(
+            Palette::parse_hex("f6cd26\nnope\n"),
+            Err(error(2, "\"nope\" isn't an RRGGBB color"))
+        );
+        assert_eq!(
+            Palette::parse_hex(""),
+            Err(error(0, "palette has no colors"))
+        );
+    }
+
+    #[test]
+    fn gpl() {
+        let text = "GIMP Palette\nName: Rust Gold 8\nColumns: 8\n# comment\n\
+                    246 205  38\tGold\n172 107 38 Orange\n";
+        let palette: Palette = text.parse().unwrap();
+        assert_eq!(palette.colors, RUST_GOLD_8[..2]);
+        assert_eq!(
+            Palette::parse_gpl("GIMP Palette\n1 2\n"),
+            Err(error(2, "\"1 2\" isn't an R G B color"))
+        );
+        assert!(Palette::parse_gpl("1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn effects() {
+        let day = Palette::default();
+        let night = day.tinted(Rgb([0, 0, 0]), 1.0);
+        assert_eq!(day.lerp(&night, 0.0), day);
+        assert_eq!(day.lerp(&night, 1.0), night);
+        assert_eq!(day.lerp(&night, 0.5).get(0), Rgb([123, 103, 19]));
+        assert_eq!(day.tinted(Rgb([255, 0, 0]), 1.0).get(3), Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    fn apply() {
+        let mut indexed = IndexedImage::new(2, 1);
+        indexed.put_pixel(1, 0, image::Luma([7]));
+        let mut img = image::RgbImage::new(2, 1);
+        Palette::default().apply(&indexed, &mut img);
+        assert_eq!(*img.get_pixel(0, 0), RUST_GOLD_8[0]);
+        assert_eq!(*img.get_pixel(1, 0), RUST_GOLD_8[7]);
+    }
+}
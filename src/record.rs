@@ -0,0 +1,283 @@
+// Flythroughs for reviewing levels and filing bugs: a camera path keyframed
+// in a text file, rendered to numbered PNGs or an animated GIF. Path files
+// have one keyframe per line, as seconds, x, y and radians:
+//
+//     # walk up the entrance hall, then look left
+//     0 240 464 1.5708
+//     2 240 380 1.5708
+//     3 240 380 3.1416
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, RgbImage};
+
+use crate::cast::{self, Camera};
+use crate::draw::{self, Shading};
+use crate::error::{self, Error, ParseError, Result};
+use crate::map::Map;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    /// Seconds from the start.
+    pub time: f32,
+    pub x: f32,
+    pub y: f32,
+    pub radians: f32,
+}
+
+/// Keyframes in time order. Between two, the camera moves in a straight
+/// line and turns the short way round.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CameraPath {
+    pub keys: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        error::load(path.as_ref())
+    }
+
+    /// The latest a keyframe can be, which keeps renders to a sane number
+    /// of frames.
+    pub const MAX_TIME: f32 = 600.0;
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |k| k.time)
+    }
+
+    /// Where the camera is at `t` seconds. Before the first keyframe and
+    /// after the last, it stays put.
+    pub fn at(&self, t: f32) -> Option<Keyframe> {
+        let next = self.keys.iter().position(|k| k.time > t);
+        let (a, b) = match next {
+            None => return self.keys.last().copied(),
+            Some(0) => return self.keys.first().copied(),
+            Some(i) => (self.keys[i - 1], self.keys[i]),
+        };
+        let s = (t - a.time) / (b.time - a.time);
+        let lerp = |from: f32, to: f32| from + (to - from) * s;
+        let tau = 2.0 * std::f32::consts::PI;
+        let turn = (b.radians - a.radians + tau / 2.0).rem_euclid(tau) - tau / 2.0;
+        Some(Keyframe {
+            time: t,
+            x: lerp(a.x, b.x),
+            y: lerp(a.y, b.y),
+            radians: a.radians + turn * s,
+        })
+    }
+
+    /// `cam` moved to where the path is at `t`.
+    pub fn camera(&self, t: f32, cam: &Camera) -> Camera {
+        let key = self.at(t);
+        Camera {
+            x: key.map_or(cam.x, |k| k.x.round() as i32),
+            y: key.map_or(cam.y, |k| k.y.round() as i32),
+            radians: key.map_or(cam.radians, |k| k.radians),
+            ..*cam
+        }
+    }
+}
+
+impl FromStr for CameraPath {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys: Vec<Keyframe> = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| ParseError {
+                line: i + 1,
+                message,
+            };
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .map(|word| word.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| error(format!("{line:?} isn't numbers")))?;
+            let [time, x, y, radians] = values[..] else {
+                return Err(error("a keyframe is time x y radians".to_string()));
+            };
+            if values.iter().any(|v| !v.is_finite()) {
+                return Err(error(format!("{line:?} isn't finite numbers")));
+            }
+            if !(0.0..=Self::MAX_TIME).contains(&time) {
+                return Err(error(format!(
+                    "time {time} has to be from 0 to {} seconds",
+                    Self::MAX_TIME
+                )));
+            }
+            if keys.last().is_some_and(|k| k.time >= time) {
+                return Err(error(format!("time {time} is out of order")));
+            }
+            keys.push(Keyframe {
+                time,
+                x,
+                y,
+                radians,
+            });
+        }
+        if keys.is_empty() {
+            return Err(ParseError {
+                line: 0,
+                message: "camera path has no keyframes".to_string(),
+            });
+        }
+        Ok(Self { keys })
+    }
+}
+
+/// Renders `path` at `fps`, from its start to its end inclusive. The rest
+/// of the camera, like the fov, comes from `cam`. Frames are only drawn as
+/// they're taken, so a long path never has to fit in memory at once.
+pub fn render<'a>(
+    map: &'a Map,
+    path: &'a CameraPath,
+    cam: &'a Camera,
+    (w, h): (u32, u32),
+    fps: f32,
+    shading: &'a Shading,
+) -> impl Iterator<Item = RgbImage> + 'a {
+    let count = (path.duration() * fps).floor() as u32 + 1;
+    (0..count).map(move |i| {
+        let cam = path.camera(i as f32 / fps, cam);
+        let view = cast::cast_columns(map, &cam, w);
+        let mut img = RgbImage::new(w, h);
+        draw::draw_view_shaded(&mut img, &view, &cam, shading);
+        img
+    })
+}
+
+/// Saves each frame as `dir/frame0000.png`, `dir/frame0001.png` and so on,
+/// making `dir` if it isn't there. Returns how many there were.
+pub fn write_png_sequence(frames: impl IntoIterator<Item = RgbImage>, dir: &Path) -> Result<usize> {
+    fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+    let mut count = 0;
+    for (i, frame) in frames.into_iter().enumerate() {
+        let path = dir.join(format!("frame{i:04}.png"));
+        frame.save(&path).map_err(|e| Error::image(&path, e))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Encodes the frames as a looping GIF played at `fps`. Returns how many
+/// there were.
+pub fn write_gif(
+    frames: impl IntoIterator<Item = RgbImage>,
+    out: impl Write,
+    fps: f32,
+) -> Result<usize> {
+    let mut encoder = GifEncoder::new(out);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(1000, fps.max(1.0).round() as u32);
+    let mut count = 0;
+    for frame in frames {
+        let rgba = image::DynamicImage::ImageRgb8(frame).into_rgba8();
+        encoder.encode_frame(image::Frame::from_parts(rgba, 0, 0, delay))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Writes the frames to `path`: a GIF if it ends in `.gif`, otherwise a
+/// directory of numbered PNGs. Returns how many there were.
+pub fn save(frames: impl IntoIterator<Item = RgbImage>, path: &Path, fps: f32) -> Result<usize> {
+    if path.extension().is_some_and(|ext| ext == "gif") {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        }
+        let file = fs::File::create(path).map_err(|e| Error::io(path, e))?;
+        write_gif(frames, std::io::BufWriter::new(file), fps).map_err(|e| match e {
+            Error::Image { source, .. } => Error::image(path, source),
+            e => e,
+        })
+    } else {
+        write_png_sequence(frames, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use image::AnimationDecoder;
+    use std::f32::consts::PI;
+
+    fn path() -> CameraPath {
+        "# up the hall\n0 240 464 1.5708\n\n2 240 384 1.5708 # stop\n3 240 384 3.1416\n"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn interpolation() {
+        let path = path();
+        assert_eq!(path.duration(), 3.0);
+        let key = path.at(1.0).unwrap();
+        assert_eq!((key.x, key.y), (240.0, 424.0));
+        assert_approx_eq!(path.at(2.5).unwrap().radians, 0.75 * PI, 1e-4);
+        assert_eq!(path.at(-1.0), path.keys.first().copied());
+        assert_eq!(path.at(10.0), path.keys.last().copied());
+
+        // across zero it turns the short way
+        let wrap: CameraPath = "0 0 0 6.0\n1 0 0 0.5".parse().unwrap();
+        let mid = wrap.at(0.5).unwrap().radians;
+        assert_approx_eq!(
+            mid.rem_euclid(2.0 * PI),
+            (6.0 + 0.5 + 2.0 * PI) / 2.0 - 2.0 * PI,
+            1e-4
+        );
+
+        let cam = path.camera(1.0, &Camera::default());
+        assert_eq!((cam.x, cam.y, cam.fov), (240, 424, PI / 3.0));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |s: &str| s.parse::<CameraPath>().err().map(|e| e.to_string());
+        assert_eq!(err(""), Some("camera path has no keyframes".to_string()));
+        assert_eq!(
+            err("0 1 2"),
+            Some("line 1: a keyframe is time x y radians".to_string())
+        );
+        assert_eq!(
+            err("0 1 2 x"),
+            Some("line 1: \"0 1 2 x\" isn't numbers".to_string())
+        );
+        assert_eq!(
+            err("1 0 0 0\n1 0 0 0"),
+            Some("line 2: time 1 is out of order".to_string())
+        );
+        assert_eq!(
+            err("1e6 0 0 0"),
+            Some("line 1: time 1000000 has to be from 0 to 600 seconds".to_string())
+        );
+        assert_eq!(
+            err("0 inf 0 0"),
+            Some("line 1: \"0 inf 0 0\" isn't finite numbers".to_string())
+        );
+    }
+
+    #[test]
+    fn gif() {
+        let map = crate::map::spooky_map();
+        let (path, cam, shading) = (path(), Camera::default(), Shading::default());
+        let frames: Vec<_> = render(&map, &path, &cam, (160, 120), 2.0, &shading).collect();
+        assert_eq!(frames.len(), 7);
+        // turned to face the other way by the end
+        assert!(frames[0] != frames[6]);
+        let mut gif = Vec::new();
+        let frames = render(&map, &path, &cam, (160, 120), 2.0, &shading);
+        assert_eq!(write_gif(frames, &mut gif, 2.0).unwrap(), 7);
+        let decoder = image::codecs::gif::GifDecoder::new(&gif[..]).unwrap();
+        let decoded = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(decoded.len(), 7);
+        assert_eq!(decoded[0].delay().numer_denom_ms(), (500, 1));
+    }
+}
@@ -0,0 +1,408 @@
+// Recording which keys the player held, tick by tick, so a session can be
+// played back exactly. The world only ever steps a whole fixed tick at a
+// time, so the same presses on the same ticks from the same camera make the
+// same game. Replay files have the camera it started with (x, y, radians,
+// fov, max distance, ray steps and projection), a line per key change or
+// view key press, and the tick the session ended on:
+//
+//     # raycaster replay
+//     map spooky
+//     tick 0.05
+//     camera 240 464 1.5707964 1.0471976 512 512 angles
+//     3 press forward
+//     12 zoom in
+//     20 release forward
+//     25 end
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::cast::{Camera, Projection};
+use crate::error::{self, ParseError, Result};
+use crate::world::{Input, World, Zoom};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Forward,
+    Back,
+    TurnLeft,
+    TurnRight,
+}
+
+impl Action {
+    pub const ALL: [Action; 4] = [
+        Action::Forward,
+        Action::Back,
+        Action::TurnLeft,
+        Action::TurnRight,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Action::Forward => "forward",
+            Action::Back => "back",
+            Action::TurnLeft => "left",
+            Action::TurnRight => "right",
+        }
+    }
+
+    fn key(self, input: &mut Input) -> &mut bool {
+        match self {
+            Action::Forward => &mut input.forward,
+            Action::Back => &mut input.back,
+            Action::TurnLeft => &mut input.turn_left,
+            Action::TurnRight => &mut input.turn_right,
+        }
+    }
+}
+
+const ZOOMS: [(Zoom, &str); 5] = [
+    (Zoom::In, "in"),
+    (Zoom::Out, "out"),
+    (Zoom::Reset, "reset"),
+    (Zoom::Nearer, "nearer"),
+    (Zoom::Further, "further"),
+];
+
+/// A key going down or up, taking effect from `tick` on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub tick: u64,
+    pub action: Action,
+    pub pressed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    /// The map it was played on, if known.
+    pub map: Option<String>,
+    /// Seconds the world steps each tick.
+    pub tick: f32,
+    /// The camera on the first tick, if known.
+    pub camera: Option<Camera>,
+    /// How many ticks the session lasted.
+    pub ticks: u64,
+    pub events: Vec<Event>,
+    /// The view keys pressed, and on which tick.
+    pub zooms: Vec<(u64, Zoom)>,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self {
+            map: None,
+            tick: 0.05,
+            camera: None,
+            ticks: 0,
+            events: Vec::new(),
+            zooms: Vec::new(),
+        }
+    }
+}
+
+impl Recording {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        error::load(path.as_ref())
+    }
+
+    /// Writes the recording, making its directory if need be.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        error::save(path.as_ref(), &self.to_string())
+    }
+
+    /// What's held after every event so far.
+    fn held(&self) -> Input {
+        let mut input = Input::default();
+        for event in &self.events {
+            *event.action.key(&mut input) = event.pressed;
+        }
+        input
+    }
+
+    /// Steps `world` one tick with `input` held, noting whichever keys
+    /// changed since the last tick and any view key.
+    pub fn step(&mut self, world: &mut World, input: &Input) {
+        if self.ticks == 0 {
+            self.camera = Some(world.camera);
+        }
+        let (mut now, mut held) = (*input, self.held());
+        for action in Action::ALL {
+            let pressed = *action.key(&mut now);
+            if *action.key(&mut held) != pressed {
+                self.events.push(Event {
+                    tick: self.ticks,
+                    action,
+                    pressed,
+                });
+            }
+        }
+        if let Some(zoom) = input.zoom {
+            self.zooms.push((self.ticks, zoom));
+        }
+        world.update(self.tick, input);
+        self.ticks += 1;
+    }
+
+    /// What was held on each tick, from the first to the last.
+    pub fn inputs(&self) -> impl Iterator<Item = Input> + '_ {
+        let mut input = Input::default();
+        let mut events = self.events.iter().peekable();
+        let mut zooms = self.zooms.iter().peekable();
+        (0..self.ticks).map(move |tick| {
+            while let Some(event) = events.next_if(|e| e.tick <= tick) {
+                *event.action.key(&mut input) = event.pressed;
+            }
+            input.zoom = zooms.next_if(|z| z.0 == tick).map(|z| z.1);
+            input
+        })
+    }
+
+    /// Steps `world` through the whole recording, calling `each` after
+    /// every tick. `world` should start out like the recorded one did,
+    /// though the camera is put back where it started if that's known.
+    pub fn replay(&self, world: &mut World, mut each: impl FnMut(&World)) {
+        if let Some(camera) = self.camera {
+            // zooming resets to the fov it started with, as when playing
+            world.camera = camera;
+            world.start_fov = camera.fov;
+        }
+        for input in self.inputs() {
+            world.update(self.tick, &input);
+            each(world);
+        }
+    }
+}
+
+fn zoom_name(zoom: Zoom) -> &'static str {
+    ZOOMS.into_iter().find(|z| z.0 == zoom).map_or("", |z| z.1)
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# raycaster replay")?;
+        if let Some(map) = &self.map {
+            writeln!(f, "map {map}")?;
+        }
+        writeln!(f, "tick {}", self.tick)?;
+        if let Some(c) = &self.camera {
+            writeln!(
+                f,
+                "camera {} {} {} {} {} {} {}",
+                c.x,
+                c.y,
+                c.radians,
+                c.fov,
+                c.max_distance,
+                c.ray_steps,
+                c.projection.name()
+            )?;
+        }
+        // key changes and view keys, in tick order
+        let mut zooms = self.zooms.iter().peekable();
+        for event in &self.events {
+            while let Some((tick, zoom)) = zooms.next_if(|z| z.0 < event.tick) {
+                writeln!(f, "{tick} zoom {}", zoom_name(*zoom))?;
+            }
+            let verb = if event.pressed { "press" } else { "release" };
+            writeln!(f, "{} {verb} {}", event.tick, event.action.name())?;
+        }
+        for (tick, zoom) in zooms {
+            writeln!(f, "{tick} zoom {}", zoom_name(*zoom))?;
+        }
+        writeln!(f, "{} end", self.ticks)
+    }
+}
+
+impl FromStr for Recording {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut recording = Recording::default();
+        let mut end = None;
+        let mut last = 0;
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| ParseError {
+                line: i + 1,
+                message,
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["map", name] => recording.map = Some(name.to_string()),
+                ["tick", seconds] => {
+                    recording.tick = seconds
+                        .parse()
+                        .ok()
+                        .filter(|&t: &f32| t > 0.0)
+                        .ok_or_else(|| error(format!("tick {seconds:?} isn't a time")))?;
+                }
+                ["camera", x, y, radians, fov, max_distance, ray_steps, projection] => {
+                    fn number<T: FromStr>(word: &str) -> Option<T> {
+                        word.parse().ok()
+                    }
+                    let camera = (|| {
+                        Some(Camera {
+                            x: number(x)?,
+                            y: number(y)?,
+                            radians: number(radians)?,
+                            fov: number(fov)?,
+                            max_distance: number(max_distance)?,
+                            ray_steps: number(ray_steps)?,
+                            projection: Projection::ALL
+                                .into_iter()
+                                .find(|p| p.name() == projection)?,
+                            ..Camera::default()
+                        })
+                    })();
+                    recording.camera =
+                        Some(camera.ok_or_else(|| error(format!("can't read {line:?}")))?);
+                }
+                [tick, verb, ..] => {
+                    let tick: u64 = tick
+                        .parse()
+                        .map_err(|_| error(format!("{tick:?} isn't a tick")))?;
+                    if end.is_some() || tick < last {
+                        return Err(error(format!("tick {tick} is out of order")));
+                    }
+                    last = tick;
+                    match (verb, &words[2..]) {
+                        ("end", []) => end = Some(tick),
+                        ("press" | "release", [name]) => {
+                            let action = Action::ALL
+                                .into_iter()
+                                .find(|a| a.name() == *name)
+                                .ok_or_else(|| error(format!("unknown key {name:?}")))?;
+                            recording.events.push(Event {
+                                tick,
+                                action,
+                                pressed: verb == "press",
+                            });
+                        }
+                        ("zoom", [name]) => {
+                            let (zoom, _) = ZOOMS
+                                .into_iter()
+                                .find(|z| z.1 == *name)
+                                .ok_or_else(|| error(format!("unknown zoom {name:?}")))?;
+                            if recording.zooms.last().is_some_and(|z| z.0 == tick) {
+                                return Err(error(format!("two zooms on tick {tick}")));
+                            }
+                            recording.zooms.push((tick, zoom));
+                        }
+                        _ => return Err(error(format!("can't read {line:?}"))),
+                    }
+                }
+                _ => return Err(error(format!("can't read {line:?}"))),
+            }
+        }
+        recording.ticks = match end {
+            Some(end) => end,
+            // without an end it stops on the last change
+            None if recording.events.is_empty() && recording.zooms.is_empty() => 0,
+            None => last + 1,
+        };
+        Ok(recording)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map;
+    use crate::palette::IndexedImage;
+
+    #[test]
+    fn text() {
+        let text = "# raycaster replay\nmap spooky\ntick 0.05\n\
+                    camera 240 464 1.5 1.0471976 512 512 plane\n\
+                    3 press forward\n3 press left\n4 zoom in\n20 release forward\n\
+                    22 zoom further\n25 end\n";
+        let recording: Recording = text.parse().unwrap();
+        assert_eq!(recording.map.as_deref(), Some("spooky"));
+        assert_eq!(recording.ticks, 25);
+        let camera = recording.camera.unwrap();
+        assert_eq!((camera.x, camera.radians), (240, 1.5));
+        assert_eq!(camera.projection, Projection::Plane);
+        assert_eq!(recording.to_string(), text);
+        let inputs: Vec<Input> = recording.inputs().collect();
+        assert_eq!(inputs.len(), 25);
+        assert!(!inputs[2].any());
+        assert!(inputs[3].forward && inputs[3].turn_left);
+        assert!(!inputs[20].forward && inputs[24].turn_left);
+        assert_eq!(inputs[4].zoom, Some(Zoom::In));
+        assert_eq!(
+            (inputs[5].zoom, inputs[22].zoom),
+            (None, Some(Zoom::Further))
+        );
+
+        let err = |s: &str| s.parse::<Recording>().err().map(|e| e.to_string());
+        assert_eq!(
+            err("3 press jump"),
+            Some("line 1: unknown key \"jump\"".to_string())
+        );
+        assert_eq!(
+            err("3 zoom sideways"),
+            Some("line 1: unknown zoom \"sideways\"".to_string())
+        );
+        assert_eq!(
+            err("3 press back\n2 release back"),
+            Some("line 2: tick 2 is out of order".to_string())
+        );
+        assert_eq!(
+            err("tick 0"),
+            Some("line 1: tick \"0\" isn't a time".to_string())
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        // walk about with the imp patrolling, then play it back from the
+        // text and check every tick lands the same. it starts off the
+        // spawn with a wider fov, which the replay has to pick up
+        let mut world = World::new(map::spooky_map());
+        world.camera.x = 250;
+        (world.camera.fov, world.start_fov) = (1.5, 1.5);
+        let mut recording = Recording::default();
+        let mut trajectory = Vec::new();
+        for tick in 0..60 {
+            let input = Input {
+                forward: (5..30).contains(&tick),
+                turn_left: (20..26).contains(&tick),
+                turn_right: tick >= 45,
+                zoom: match tick {
+                    8 => Some(Zoom::In),
+                    30 => Some(Zoom::Further),
+                    40 => Some(Zoom::Reset),
+                    50 => Some(Zoom::Out),
+                    _ => None,
+                },
+                ..Input::default()
+            };
+            recording.step(&mut world, &input);
+            let cam = &world.camera;
+            trajectory.push((cam.x, cam.y, cam.radians, cam.fov, cam.max_distance));
+        }
+        let mut last = IndexedImage::new(160, 100);
+        world.render(&mut last);
+
+        let recording: Recording = recording.to_string().parse().unwrap();
+        assert_eq!(recording.ticks, 60);
+        let mut replayed = World::new(map::spooky_map());
+        let mut again = Vec::new();
+        recording.replay(&mut replayed, |w| {
+            let cam = &w.camera;
+            again.push((cam.x, cam.y, cam.radians, cam.fov, cam.max_distance));
+        });
+        assert_eq!(again, trajectory);
+        assert_eq!(replayed.time, world.time);
+        assert_eq!(
+            (replayed.actors[0].x, replayed.actors[0].y),
+            (world.actors[0].x, world.actors[0].y)
+        );
+        let mut frame = IndexedImage::new(160, 100);
+        replayed.render(&mut frame);
+        assert!(frame == last);
+    }
+}
@@ -0,0 +1,155 @@
+// Visibility queries for game logic, in map pixels. They march rays the same
+// way the view does, so what a monster can see matches what gets drawn:
+// solid walls block sight and see-through ones don't.
+use std::f32::consts::PI;
+
+use crate::cast::{self, Hits};
+use crate::map::Grid;
+
+/// A cone to look through: centred on `radians`, `width` radians across.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fov {
+    pub radians: f32,
+    pub width: f32,
+}
+
+impl Fov {
+    /// Every direction at once.
+    pub const ALL: Fov = Fov {
+        radians: 0.0,
+        width: 2.0 * PI,
+    };
+}
+
+/// Which cells of a grid can be seen, from `visible_cells`.
+pub struct Visible {
+    pub w: u32,
+    pub h: u32,
+    cells: Vec<bool>,
+}
+
+impl Visible {
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x < self.w && y < self.h && self.cells[(x + y * self.w) as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let w = self.w;
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, &seen)| seen)
+            .map(move |(i, _)| (i as u32 % w, i as u32 / w))
+    }
+}
+
+fn angle_to(from: (i32, i32), to: (i32, i32)) -> f32 {
+    // minus because +y is down, same as the rays
+    ((from.1 - to.1) as f32).atan2((to.0 - from.0) as f32)
+}
+
+/// Whether nothing solid stands between `from` and `to`. Walls at `to`
+/// itself don't count, so a wall can be seen.
+pub fn line_of_sight<G: Grid>(grid: &G, from: (i32, i32), to: (i32, i32)) -> bool {
+    let (dx, dy) = ((to.0 - from.0) as f32, (to.1 - from.1) as f32);
+    let distance = (dx * dx + dy * dy).sqrt();
+    // a step a pixel, stopping just short of `to`
+    let steps = distance.ceil() as u32;
+    !Hits::new(grid, from, angle_to(from, to), distance, steps)
+        .any(|hit| !grid.is_transparent(&hit.wall))
+}
+
+/// Every cell within `radius` of `origin` and inside `fov` that a ray
+/// reaches, including the faces of the walls that stop them.
+pub fn visible_cells<G: Grid>(grid: &G, origin: (i32, i32), fov: Fov, radius: f32) -> Visible {
+    let (w, h) = grid.dimensions();
+    let mut visible = Visible {
+        w,
+        h,
+        cells: vec![false; (w * h) as usize],
+    };
+    // enough rays that neighbours are at most a pixel apart at `radius`
+    let rays = (fov.width * radius).ceil().max(1.0) as u32;
+    let steps = radius.ceil() as u32;
+    for i in 0..=rays {
+        let angle = fov.radians + fov.width * (i as f32 / rays as f32 - 0.5);
+        let reach = Hits::new(grid, origin, angle, radius, steps)
+            .find(|hit| !grid.is_transparent(&hit.wall))
+            .map_or(radius, |hit| hit.distance);
+        for step in 0..steps {
+            let dist = radius * step as f32 / steps as f32;
+            if dist > reach {
+                break;
+            }
+            let (x_off, y_off) = cast::calculate_ray(dist, angle);
+            let (x, y) = (origin.0 + x_off, origin.1 - y_off);
+            if x < 0 || y < 0 || x as u32 >= w || y as u32 >= h {
+                break;
+            }
+            visible.cells[(x as u32 + y as u32 * w) as usize] = true;
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Map, Wall};
+
+    fn room() -> Map {
+        // 128x64, a stone wall at x 60..68 for y < 48 and a crystal pane
+        // at x 100..104 below it
+        let mut map = Map::new(128, 64);
+        for y in 0..64 {
+            for x in 0..128 {
+                map.map[x + y * 128] = match (x, y) {
+                    (60..=67, 0..=47) => Some(Wall::Stone),
+                    (100..=103, 48..) => Some(Wall::Crystal),
+                    _ => None,
+                };
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn sight_lines() {
+        let map = room();
+        assert!(line_of_sight(&map, (30, 20), (50, 40)));
+        assert!(!line_of_sight(&map, (30, 20), (90, 20)));
+        assert!(!line_of_sight(&map, (90, 20), (30, 20)));
+        // around the wall's end, and through glass
+        assert!(line_of_sight(&map, (30, 56), (90, 56)));
+        assert!(line_of_sight(&map, (90, 56), (120, 56)));
+        // looking at the wall itself
+        assert!(line_of_sight(&map, (30, 20), (60, 20)));
+        assert!(line_of_sight(&map, (30, 20), (30, 20)));
+    }
+
+    #[test]
+    fn visible() {
+        let map = room();
+        let all = visible_cells(&map, (30, 20), Fov::ALL, 100.0);
+        assert!(all.contains(30, 20) && all.contains(0, 0));
+        assert!(all.contains(60, 20) && !all.contains(61, 20));
+        assert!(!all.contains(90, 20));
+        assert!(all.contains(70, 60));
+        assert!(all.iter().all(|(x, y)| !(62..68).contains(&x) || y >= 48));
+
+        // facing left, what's to the right is out of view, and the radius
+        // cuts off the far side
+        let left = visible_cells(
+            &map,
+            (80, 56),
+            Fov {
+                radians: PI,
+                width: PI / 2.0,
+            },
+            40.0,
+        );
+        assert!(left.contains(50, 56) && !left.contains(30, 56));
+        assert!(!left.contains(90, 56));
+        assert!(left.iter().all(|(x, _)| x <= 80));
+    }
+}
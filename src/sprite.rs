@@ -0,0 +1,229 @@
+// Flat pictures stood up in the 3D view, always facing the camera, and
+// hidden behind walls that are closer.
+use std::f32::consts::PI;
+
+use crate::cast::{self, Camera, Ray, WALL_HEIGHT};
+use crate::frame::Frame;
+use crate::map::Wall;
+use crate::palette::{IndexedImage, RUST_GOLD_8 as PALETTE};
+
+/// A picture in palette indices, `None` where it's see-through.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprite {
+    pub w: u32,
+    pub h: u32,
+    pub pixels: Vec<Option<u8>>,
+}
+
+impl Sprite {
+    pub fn get(&self, x: u32, y: u32) -> Option<u8> {
+        self.pixels[(x + y * self.w) as usize]
+    }
+}
+
+/// One sprite per direction something can be seen from, going round
+/// counterclockwise starting from its front.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Directional {
+    pub frames: Vec<Sprite>,
+}
+
+impl Directional {
+    /// The frame for a viewer standing `angle` radians counterclockwise
+    /// from the way the thing faces.
+    pub fn frame(&self, angle: f32) -> &Sprite {
+        let n = self.frames.len();
+        let turn = angle.rem_euclid(2.0 * PI) / (2.0 * PI);
+        &self.frames[(turn * n as f32).round() as usize % n]
+    }
+
+    /// A stand-in 16x24 figure in eight directions: a `body` colored blob
+    /// whose `eyes` show which way it's looking.
+    pub fn figure(body: u8, eyes: u8) -> Self {
+        let frames = (0..8)
+            .map(|i| {
+                // how far round from the front the viewer is. the eyes
+                // slide across as it turns away, and from behind there's
+                // no face to see
+                let turn = i as f32 * PI / 4.0;
+                let (w, h) = (16, 24);
+                let mut pixels = vec![None; w * h];
+                for y in 0..h {
+                    for x in 0..w {
+                        let (dx, dy) = (x as f32 - 7.5, y as f32 - 13.0);
+                        if (dx / 7.0).powi(2) + (dy / 11.0).powi(2) <= 1.0 {
+                            pixels[x + y * w] = Some(body);
+                        }
+                    }
+                }
+                if turn.cos() > -0.1 {
+                    let centre = 7.5 - 5.0 * turn.sin();
+                    let spread = 3.0 * turn.cos();
+                    for eye in [centre - spread, centre + spread] {
+                        let x = eye.round().clamp(0.0, 15.0) as usize;
+                        pixels[x + 8 * w] = Some(eyes);
+                        pixels[x + 9 * w] = Some(eyes);
+                    }
+                }
+                Sprite {
+                    w: w as u32,
+                    h: h as u32,
+                    pixels,
+                }
+            })
+            .collect();
+        Self { frames }
+    }
+}
+
+/// A sprite standing on the floor at a point on the map.
+#[derive(Clone, Copy)]
+pub struct Billboard<'a> {
+    pub x: f32,
+    pub y: f32,
+    pub sprite: &'a Sprite,
+}
+
+fn fill_billboards<C>(
+    buf: &mut [C],
+    (w, h): (u32, u32),
+    view: &[Ray<Wall>],
+    cam: &Camera,
+    billboards: &[Billboard],
+    (channels, px): (usize, impl Fn(u8, &mut [C])),
+) {
+    let columns = view.len() as f32;
+    let horizon = h as f32 / 2.0;
+    let mut sorted: Vec<(f32, &Billboard)> = billboards
+        .iter()
+        .map(|b| {
+            let (dx, dy) = (b.x - cam.x as f32, b.y - cam.y as f32);
+            ((dx * dx + dy * dy).sqrt(), b)
+        })
+        .collect();
+    // far to near, so near ones cover far ones
+    sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (distance, b) in sorted {
+        if distance < 1.0 {
+            continue;
+        }
+        // the same angle to column mapping as the view, which is cast
+        // right to left
+        let angle = (cam.y as f32 - b.y).atan2(b.x - cam.x as f32);
+        let off = (angle - cam.radians + PI).rem_euclid(2.0 * PI) - PI;
+        let centre = w as f32 - 1.0 - cast::calculate_span(cam, off) * columns;
+        let from_axis = cast::calculate_height(distance, angle, cam, view.len());
+        // a sprite as many pixels high as a wall is tall stands as tall
+        let scale = 2.0 * from_axis / WALL_HEIGHT;
+        let sprite = b.sprite;
+        let (sw, sh) = (sprite.w as f32 * scale, sprite.h as f32 * scale);
+        let (left, top) = (centre - sw / 2.0, horizon + from_axis - sh);
+        let xs = left.max(0.0) as u32..(left + sw).clamp(0.0, w as f32) as u32;
+        for x in xs {
+            // walls in front hide it
+            let column = w as usize - 1 - x as usize;
+            if view.get(column).is_none_or(|ray| ray.distance <= distance) {
+                continue;
+            }
+            let u = ((x as f32 - left) / scale) as u32;
+            let ys = top.max(0.0) as u32..(top + sh).clamp(0.0, h as f32) as u32;
+            for y in ys {
+                let v = ((y as f32 - top) / scale) as u32;
+                if let Some(idx) = sprite.get(u.min(sprite.w - 1), v.min(sprite.h - 1)) {
+                    let i = channels * (x + y * w) as usize;
+                    px(idx, &mut buf[i..i + channels]);
+                }
+            }
+        }
+    }
+}
+
+/// Draws billboards over a view drawn with `draw::draw_view` from the same
+/// rays.
+pub fn draw_billboards<F: Frame>(
+    img: &mut F,
+    view: &[Ray<Wall>],
+    cam: &Camera,
+    billboards: &[Billboard],
+) {
+    let dims = img.dimensions();
+    let px = |idx: u8, out: &mut [F::Channel]| F::encode(PALETTE[idx as usize], out);
+    fill_billboards(
+        img.channels_mut(),
+        dims,
+        view,
+        cam,
+        billboards,
+        (F::CHANNELS, px),
+    );
+}
+
+pub fn draw_billboards_indexed(
+    img: &mut IndexedImage,
+    view: &[Ray<Wall>],
+    cam: &Camera,
+    billboards: &[Billboard],
+) {
+    let dims = img.dimensions();
+    let px = |idx, out: &mut [u8]| out[0] = idx;
+    fill_billboards(img, dims, view, cam, billboards, (1, px));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Map;
+
+    #[test]
+    fn frames() {
+        let figure = Directional::figure(1, 0);
+        let eyes = |s: &Sprite| {
+            (0..16)
+                .filter(|&x| s.get(x, 8) == Some(0))
+                .collect::<Vec<_>>()
+        };
+        // two eyes from the front, none from behind, one side from the side
+        assert_eq!(eyes(figure.frame(0.0)), [5, 11]);
+        assert_eq!(eyes(figure.frame(PI)), []);
+        assert_eq!(figure.frame(0.1), figure.frame(-0.1));
+        assert_eq!(figure.frame(PI / 2.0), &figure.frames[2]);
+        assert_eq!(figure.frame(-PI / 2.0), &figure.frames[6]);
+    }
+
+    #[test]
+    fn billboards() {
+        let mut map = Map::new(512, 512);
+        for y in 0..512 {
+            map.map[300 + y * 512] = Some(Wall::Stone);
+        }
+        let cam = Camera {
+            x: 60,
+            y: 256,
+            ..Camera::default()
+        };
+        let view = cast::cast_fov(&map, &cam);
+        let sprite = Sprite {
+            w: 2,
+            h: 2,
+            pixels: vec![Some(4); 4],
+        };
+        let draw = |x| {
+            let mut img = IndexedImage::new(512, 512);
+            let billboards = [Billboard {
+                x,
+                y: 256.0,
+                sprite: &sprite,
+            }];
+            draw_billboards_indexed(&mut img, &view, &cam, &billboards);
+            img
+        };
+        let count = |img: &IndexedImage| img.pixels().filter(|p| p.0[0] == 4).count();
+        // in the middle of the view, standing on the floor 55 rows down
+        let near = draw(200.0);
+        assert_eq!(near.get_pixel(256, 310).0, [4]);
+        assert_eq!(near.get_pixel(256, 311).0, [0]);
+        // further is smaller, behind the wall is hidden
+        assert!(count(&draw(280.0)) < count(&near));
+        assert_eq!(count(&draw(400.0)), 0);
+    }
+}
@@ -0,0 +1,277 @@
+// Playing in a terminal, for boxes without a display. Each character cell
+// shows two pixels stacked, using the upper half block with the top pixel
+// as its foreground color and the bottom one as its background.
+use std::env;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{cursor, execute, terminal};
+use image::Rgb;
+
+use crate::error::Result;
+use crate::frame::Buffer;
+use crate::palette::{IndexedImage, Palette};
+use crate::world::{Input, World, Zoom};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// 24-bit color, which most modern terminals do.
+    TrueColor,
+    /// The xterm 256 color palette, for the ones that don't.
+    Ansi256,
+}
+
+impl ColorMode {
+    /// Truecolor when `COLORTERM` says so, like most terminals that have
+    /// it set, otherwise the 256 color fallback.
+    pub fn detect() -> Self {
+        match env::var("COLORTERM").as_deref() {
+            Ok("truecolor" | "24bit") => ColorMode::TrueColor,
+            _ => ColorMode::Ansi256,
+        }
+    }
+}
+
+/// The nearest entry in xterm's 256 colors: the 6x6x6 cube or the gray ramp.
+pub fn ansi_256(Rgb([r, g, b]): Rgb<u8>) -> u8 {
+    // the cube's levels are 0, 95, 135, 175, 215 and 255
+    let level = |c: u8| {
+        if c < 48 {
+            0
+        } else {
+            ((c as u32 - 35) / 40) as u8
+        }
+    };
+    let value = |l: u8| if l == 0 { 0 } else { 55 + 40 * l as u32 };
+    let (lr, lg, lb) = (level(r), level(g), level(b));
+    let cube = 16 + 36 * lr + 6 * lg + lb;
+    // 24 grays from 8 to 238
+    let mean = (r as u32 + g as u32 + b as u32) / 3;
+    let step = ((mean.saturating_sub(3)) / 10).min(23);
+    let gray = 8 + 10 * step;
+    let dist = |(x, y, z): (u32, u32, u32)| {
+        let d = |a: u8, b: u32| (a as i32 - b as i32).pow(2);
+        d(r, x) + d(g, y) + d(b, z)
+    };
+    let cube_dist = dist((value(lr), value(lg), value(lb)));
+    if dist((gray, gray, gray)) < cube_dist {
+        232 + step as u8
+    } else {
+        cube
+    }
+}
+
+fn color(out: &mut String, layer: u8, c: Rgb<u8>, mode: ColorMode) {
+    // layer is 38 for the foreground and 48 for the background
+    let _ = match mode {
+        ColorMode::TrueColor => write!(out, "\x1b[{layer};2;{};{};{}m", c[0], c[1], c[2]),
+        ColorMode::Ansi256 => write!(out, "\x1b[{layer};5;{}m", ansi_256(c)),
+    };
+}
+
+/// Writes `frame` as rows of half blocks, each row starting with a cursor
+/// move so the frame lands at the top left whatever was there before. An
+/// odd last row gets black beneath it.
+pub fn encode(frame: &Buffer, mode: ColorMode, out: &mut String) {
+    let black = Rgb([0, 0, 0]);
+    for row in 0..frame.h.div_ceil(2) {
+        let _ = write!(out, "\x1b[{};1H", row + 1);
+        let mut last = None;
+        for x in 0..frame.w {
+            let top = frame.pixels[(x + 2 * row * frame.w) as usize];
+            let bottom = if 2 * row + 1 < frame.h {
+                frame.pixels[(x + (2 * row + 1) * frame.w) as usize]
+            } else {
+                black
+            };
+            // only say the colors when they change
+            if last != Some((top, bottom)) {
+                color(out, 38, top, mode);
+                color(out, 48, bottom, mode);
+                last = Some((top, bottom));
+            }
+            out.push('▀');
+        }
+    }
+    out.push_str("\x1b[0m");
+}
+
+// most terminals only send key presses, and repeats while held, so a key
+// counts as held until it's gone quiet for a while: long enough at first to
+// wait out the delay before repeats start, then only a little past each
+// repeat. terminals that can report releases say exactly instead
+const FIRST_HOLD: Duration = Duration::from_millis(700);
+const REPEAT_HOLD: Duration = Duration::from_millis(150);
+
+// which of forward, back, left and right a key is
+fn movement(code: KeyCode) -> Option<usize> {
+    match code {
+        KeyCode::Char('w') | KeyCode::Up => Some(0),
+        KeyCode::Char('s') | KeyCode::Down => Some(1),
+        KeyCode::Char('a') | KeyCode::Left => Some(2),
+        KeyCode::Char('d') | KeyCode::Right => Some(3),
+        _ => None,
+    }
+}
+
+/// Runs `world` in the terminal until Q, Esc or ctrl+C. WASD or the arrow
+/// keys move, = and - zoom, 0 unzooms, [ and ] change how far it sees, and
+/// the view follows the terminal's size.
+pub fn run(world: &mut World, palette: &Palette) -> Result<()> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    let mut restore = Restore { releases: false };
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    if terminal::supports_keyboard_enhancement().unwrap_or(false) {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+        restore.releases = true;
+    }
+    Ok(play(world, palette, &mut stdout, restore.releases)?)
+}
+
+// puts the terminal back however playing ends, errors and panics included
+struct Restore {
+    releases: bool,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        // nothing more can be done if these fail too
+        if self.releases {
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn play(
+    world: &mut World,
+    palette: &Palette,
+    stdout: &mut io::Stdout,
+    releases: bool,
+) -> io::Result<()> {
+    let mode = ColorMode::detect();
+    let tick = Duration::from_millis(50);
+    // when each of forward, back, left and right was last pressed, and how
+    // long that keeps it held. with releases it's held until let go
+    let mut pressed: [Option<(Instant, Duration)>; 4] = [None; 4];
+    let mut last_tick = Instant::now();
+    let mut text = String::new();
+    let mut zoom = None;
+    loop {
+        while event::poll(tick.saturating_sub(last_tick.elapsed()))? {
+            match event::read()? {
+                Event::Key(KeyEvent {
+                    code,
+                    kind: KeyEventKind::Release,
+                    ..
+                }) => {
+                    if let Some(key) = movement(code) {
+                        pressed[key] = None;
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press | KeyEventKind::Repeat,
+                    ..
+                }) => {
+                    let key = match code {
+                        KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                            return Ok(());
+                        }
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Char(c @ ('=' | '+' | '-' | '0' | '[' | ']')) => {
+                            zoom = Some(match c {
+                                '=' | '+' => Zoom::In,
+                                '-' => Zoom::Out,
+                                '0' => Zoom::Reset,
+                                '[' => Zoom::Nearer,
+                                _ => Zoom::Further,
+                            });
+                            continue;
+                        }
+                        code => match movement(code) {
+                            Some(key) => key,
+                            None => continue,
+                        },
+                    };
+                    let hold = match pressed[key] {
+                        _ if releases => Duration::MAX,
+                        Some((at, hold)) if at.elapsed() < hold => REPEAT_HOLD,
+                        _ => FIRST_HOLD,
+                    };
+                    pressed[key] = Some((Instant::now(), hold));
+                }
+                Event::Resize(..) => execute!(stdout, terminal::Clear(terminal::ClearType::All))?,
+                _ => {}
+            }
+        }
+        let held = |key: usize| pressed[key].is_some_and(|(at, hold)| at.elapsed() < hold);
+        let input = Input {
+            forward: held(0),
+            back: held(1),
+            turn_left: held(2),
+            turn_right: held(3),
+            zoom: zoom.take(),
+        };
+        world.update(last_tick.elapsed().as_secs_f32(), &input);
+        last_tick = Instant::now();
+
+        let (cols, rows) = terminal::size()?;
+        let (w, h) = (cols as u32, 2 * rows as u32);
+        if w == 0 || h == 0 {
+            continue;
+        }
+        let mut indexed = IndexedImage::new(w, h);
+        world.render(&mut indexed);
+        let mut frame = Buffer::new(w, h);
+        palette.apply(&indexed, &mut frame);
+        text.clear();
+        encode(&frame, mode, &mut text);
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_256() {
+        assert_eq!(ansi_256(Rgb([0, 0, 0])), 16);
+        assert_eq!(ansi_256(Rgb([255, 255, 255])), 231);
+        assert_eq!(ansi_256(Rgb([255, 0, 0])), 196);
+        assert_eq!(ansi_256(Rgb([95, 135, 175])), 67);
+        // grays in between the cube's levels use the ramp
+        assert_eq!(ansi_256(Rgb([128, 128, 128])), 244);
+        assert_eq!(ansi_256(Rgb([32, 32, 32])), 234);
+    }
+
+    #[test]
+    fn half_blocks() {
+        let mut frame = Buffer::new(2, 3);
+        frame.pixels[0] = Rgb([255, 0, 0]);
+        frame.pixels[1] = Rgb([255, 0, 0]);
+        let mut out = String::new();
+        encode(&frame, ColorMode::TrueColor, &mut out);
+        assert_eq!(
+            out,
+            "\x1b[1;1H\x1b[38;2;255;0;0m\x1b[48;2;0;0;0m▀▀\
+             \x1b[2;1H\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m▀▀\x1b[0m"
+        );
+        out.clear();
+        encode(&frame, ColorMode::Ansi256, &mut out);
+        assert!(out.starts_with("\x1b[1;1H\x1b[38;5;196m\x1b[48;5;16m▀▀"));
+    }
+}
@@ -0,0 +1,308 @@
+// Gameplay raycasts: what a shot, a use key or a monster's glance runs into
+// first, walls or things. Walls come from the same marching as the view;
+// things are circles around their position.
+use crate::cast::{self, Camera, Hits, Side};
+use crate::map::{Grid, Map, Thing, Wall};
+
+/// What a trace ran into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Wall {
+        wall: Wall,
+        x: u32,
+        y: u32,
+    },
+    /// An index into the map's `things`.
+    Thing(usize),
+    /// An index into the world's `actors`.
+    Actor(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceHit {
+    pub distance: f32,
+    /// Where it hit, in map pixels.
+    pub point: (f32, f32),
+    /// Unit vector out of the surface that was hit, in map pixels (+y down).
+    pub normal: (f32, f32),
+    pub target: Target,
+}
+
+/// A ray fired from anywhere in any direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trace {
+    pub x: f32,
+    pub y: f32,
+    pub radians: f32,
+    pub range: f32,
+    /// How big things are to hit.
+    pub thing_radius: f32,
+    /// Carry on through glass and grates instead of stopping at them.
+    pub pierce: bool,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            radians: 0.0,
+            range: 512.0,
+            thing_radius: 8.0,
+            pierce: false,
+        }
+    }
+}
+
+impl Trace {
+    /// Straight out of the middle of the camera, for "what am I looking at".
+    pub fn from_camera(cam: &Camera) -> Self {
+        Self {
+            x: cam.x as f32,
+            y: cam.y as f32,
+            radians: cam.radians,
+            range: cam.max_distance,
+            ..Self::default()
+        }
+    }
+
+    fn direction(&self) -> (f32, f32) {
+        // minus because +y is down
+        (self.radians.cos(), -self.radians.sin())
+    }
+
+    /// The first wall or thing along the trace. Things the trace starts
+    /// inside, like whoever fired it, are passed over.
+    pub fn cast(&self, map: &Map) -> Option<TraceHit> {
+        self.cast_filtered(map, |_| true)
+    }
+
+    /// Like `cast`, only things `keep` accepts can be hit.
+    pub fn cast_filtered(&self, map: &Map, keep: impl Fn(&Thing) -> bool) -> Option<TraceHit> {
+        let wall = self.wall_hit(map);
+        let reach = wall.map_or(self.range, |hit| hit.distance);
+        let thing = map
+            .things
+            .iter()
+            .enumerate()
+            .filter(|(_, thing)| keep(thing))
+            .filter_map(|(i, thing)| {
+                self.circle_hit((thing.x as f32, thing.y as f32), Target::Thing(i))
+            })
+            .filter(|hit| hit.distance < reach)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance));
+        thing.or(wall)
+    }
+
+    fn wall_hit(&self, map: &Map) -> Option<TraceHit> {
+        let origin = (self.x.round() as i32, self.y.round() as i32);
+        let steps = self.range.ceil() as u32;
+        let hit = Hits::new(map, origin, self.radians, self.range, steps)
+            .find(|hit| !(self.pierce && map.is_transparent(&hit.wall)))?;
+        let (dx, dy) = self.direction();
+        let start = (origin.0 as f32, origin.1 as f32);
+        // a segment's face is square to the segment, whichever way it runs.
+        // it's the one the marching found at exactly that distance
+        let segment = map.segments.iter().find(|segment| {
+            segment.wall == hit.wall
+                && cast::intersect(start, self.radians, segment) == Some(hit.distance)
+        });
+        let normal = match (segment, hit.side) {
+            (Some(segment), _) => {
+                let (sx, sy) = (segment.to.0 - segment.from.0, segment.to.1 - segment.from.1);
+                let length = segment.length();
+                let (nx, ny) = (-sy / length, sx / length);
+                // facing back towards whoever fired
+                if nx * dx + ny * dy > 0.0 {
+                    (-nx, -ny)
+                } else {
+                    (nx, ny)
+                }
+            }
+            (None, Side::X) => (-dx.signum(), 0.0),
+            (None, Side::Y) => (0.0, -dy.signum()),
+        };
+        Some(TraceHit {
+            distance: hit.distance,
+            point: (self.x + dx * hit.distance, self.y + dy * hit.distance),
+            normal,
+            target: Target::Wall {
+                wall: hit.wall,
+                x: hit.x,
+                y: hit.y,
+            },
+        })
+    }
+
+    /// Where the trace first enters a `thing_radius` circle around
+    /// `centre`, if it does, as a hit on `target`.
+    pub(crate) fn circle_hit(&self, centre: (f32, f32), target: Target) -> Option<TraceHit> {
+        let (dx, dy) = self.direction();
+        let (fx, fy) = (self.x - centre.0, self.y - centre.1);
+        let b = fx * dx + fy * dy;
+        let c = fx * fx + fy * fy - self.thing_radius * self.thing_radius;
+        let disc = b * b - c;
+        if disc < 0.0 {
+            return None;
+        }
+        let t = -b - disc.sqrt();
+        if t < 0.0 || t > self.range {
+            return None;
+        }
+        let point = (self.x + dx * t, self.y + dy * t);
+        let normal = (
+            (point.0 - centre.0) / self.thing_radius,
+            (point.1 - centre.1) / self.thing_radius,
+        );
+        Some(TraceHit {
+            distance: t,
+            point,
+            normal,
+            target,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Segment, ThingKind};
+    use assert_approx_eq::assert_approx_eq;
+    use std::f32::consts::PI;
+
+    fn room() -> Map {
+        // 128x64 with a stone wall at x >= 100 and a crystal pane at x 50
+        let mut map = Map::new(128, 64);
+        for y in 0..64 {
+            map.map[50 + y * 128] = Some(Wall::Crystal);
+            for x in 100..128 {
+                map.map[x + y * 128] = Some(Wall::Stone);
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn walls() {
+        let map = room();
+        let shot = Trace {
+            x: 60.0,
+            y: 32.0,
+            ..Trace::default()
+        };
+        let hit = shot.cast(&map).unwrap();
+        assert_eq!(
+            hit.target,
+            Target::Wall {
+                wall: Wall::Stone,
+                x: 100,
+                y: 32
+            }
+        );
+        assert_approx_eq!(hit.distance, 40.0);
+        assert_eq!(hit.normal, (-1.0, 0.0));
+        assert_approx_eq!(hit.point.0, 100.0);
+
+        // back the other way the glass stops it, unless it pierces
+        let back = Trace {
+            radians: PI,
+            ..shot
+        };
+        let hit = back.cast(&map).unwrap();
+        assert!(matches!(
+            hit.target,
+            Target::Wall {
+                wall: Wall::Crystal,
+                ..
+            }
+        ));
+        assert_eq!(hit.normal, (1.0, 0.0));
+        let pierce = Trace {
+            pierce: true,
+            ..back
+        };
+        assert_eq!(pierce.cast(&map), None);
+
+        // out of range
+        let short = Trace {
+            range: 20.0,
+            ..shot
+        };
+        assert_eq!(short.cast(&map), None);
+    }
+
+    #[test]
+    fn segments() {
+        // a diagonal running down to the right, shot at from the left and
+        // from below: either way the normal is square to it
+        let mut map = room();
+        map.segments
+            .push(Segment::new((20.0, 10.0), (40.0, 30.0), Wall::Brick));
+        let shot = Trace {
+            x: 10.0,
+            y: 20.0,
+            ..Trace::default()
+        };
+        let hit = shot.cast(&map).unwrap();
+        assert_approx_eq!(hit.distance, 20.0);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_approx_eq!(hit.normal.0, -half);
+        assert_approx_eq!(hit.normal.1, half);
+
+        let up = Trace {
+            x: 30.0,
+            y: 40.0,
+            radians: PI / 2.0,
+            ..Trace::default()
+        };
+        let hit = up.cast(&map).unwrap();
+        assert_approx_eq!(hit.distance, 20.0);
+        assert_approx_eq!(hit.normal.0, -half);
+        assert_approx_eq!(hit.normal.1, half);
+
+        // from the other side it faces the other way
+        let down = Trace {
+            x: 30.0,
+            y: 5.0,
+            radians: -PI / 2.0,
+            ..Trace::default()
+        };
+        let hit = down.cast(&map).unwrap();
+        assert_approx_eq!(hit.normal.0, half);
+        assert_approx_eq!(hit.normal.1, -half);
+    }
+
+    #[test]
+    fn things() {
+        let mut map = room();
+        map.add_thing(ThingKind::Item, "key", 90, 32);
+        map.add_thing(ThingKind::Enemy, "imp", 80, 32);
+        map.add_thing(ThingKind::Enemy, "ghost", 120, 32);
+        let shot = Trace {
+            x: 60.0,
+            y: 32.0,
+            thing_radius: 4.0,
+            ..Trace::default()
+        };
+        let hit = shot.cast(&map).unwrap();
+        assert_eq!(hit.target, Target::Thing(1));
+        assert_approx_eq!(hit.distance, 16.0);
+        assert_eq!(hit.normal, (-1.0, 0.0));
+        let hit = shot.cast_filtered(&map, |t| t.name != "imp").unwrap();
+        assert_eq!(hit.target, Target::Thing(0));
+
+        // the imp firing from where it stands doesn't hit itself, and the
+        // ghost inside the wall is out of reach
+        let from_imp = Trace { x: 80.0, ..shot };
+        assert_eq!(from_imp.cast(&map).unwrap().target, Target::Thing(0));
+        let hit = from_imp.cast_filtered(&map, |t| t.kind == ThingKind::Enemy);
+        assert!(matches!(hit.unwrap().target, Target::Wall { .. }));
+
+        // grazing past misses
+        let high = Trace { y: 27.0, ..shot };
+        assert!(matches!(
+            high.cast(&map).unwrap().target,
+            Target::Wall { .. }
+        ));
+    }
+}
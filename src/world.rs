@@ -0,0 +1,318 @@
+// Everything a running game needs in one place, so an app only has to feed
+// it input and show its frames, with or without a window.
+use std::f32::consts::PI;
+
+use crate::actor::{Actor, NavGrid};
+use crate::cast::{self, Camera};
+use crate::draw::{self, Shading};
+use crate::frame::Frame;
+use crate::light::Lightmap;
+use crate::map::{Map, ThingKind};
+use crate::palette::IndexedImage;
+use crate::sprite::{self, Directional};
+use crate::trace::{Target, Trace, TraceHit};
+
+/// Which movement keys are held this tick, and any view key pressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Input {
+    pub forward: bool,
+    pub back: bool,
+    pub turn_left: bool,
+    pub turn_right: bool,
+    pub zoom: Option<Zoom>,
+}
+
+/// The keys that change how the camera sees rather than where it is. Each
+/// press does its bit once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Zoom {
+    In,
+    Out,
+    /// Back to `World::start_fov`.
+    Reset,
+    /// See less far.
+    Nearer,
+    /// See further.
+    Further,
+}
+
+impl Input {
+    /// Whether any movement key is held.
+    pub fn any(&self) -> bool {
+        self.forward || self.back || self.turn_left || self.turn_right
+    }
+}
+
+pub struct World {
+    pub map: Map,
+    pub camera: Camera,
+    pub actors: Vec<Actor>,
+    pub lights: Option<Lightmap>,
+    pub nav: NavGrid,
+    pub sprites: Directional,
+    /// Fog, dithering and so on. The world's own lights are added when
+    /// rendering, so leave `lights` empty here.
+    pub shading: Shading<'static>,
+    /// Map pixels per second.
+    pub move_speed: f32,
+    /// Radians per second.
+    pub turn_speed: f32,
+    /// Seconds since the world started.
+    pub time: f32,
+    /// The fov `Zoom::Reset` goes back to.
+    pub start_fov: f32,
+    // where the camera really is, between whole pixels
+    pos: (f32, f32),
+}
+
+impl World {
+    /// Puts the camera at the map's spawn and an actor on every enemy.
+    pub fn new(map: Map) -> Self {
+        let camera = Camera {
+            x: map.spawn.x,
+            y: map.spawn.y,
+            radians: map.spawn.radians,
+            ..Camera::default()
+        };
+        let actors = map.enemies().map(|t| Actor::from_thing(t, &map)).collect();
+        let mut world = Self {
+            lights: None,
+            nav: NavGrid::new(&map, 16),
+            pos: (camera.x as f32, camera.y as f32),
+            map,
+            camera,
+            actors,
+            sprites: Directional::figure(5, 0),
+            shading: Shading::default(),
+            move_speed: 160.0,
+            turn_speed: PI,
+            time: 0.0,
+            start_fov: camera.fov,
+        };
+        world.map_changed();
+        world
+    }
+
+    /// Call after editing the map, so lighting and pathfinding catch up.
+    pub fn map_changed(&mut self) {
+        self.lights = (!self.map.lights.is_empty()).then(|| Lightmap::bake(&self.map, 8, 0.25));
+        if let Some(lights) = &mut self.lights {
+            lights.update(self.time);
+        }
+        self.nav = NavGrid::new(&self.map, 16);
+    }
+
+    /// Whether anything changes on its own, without input, so an app
+    /// knows to keep ticking.
+    pub fn animates(&self) -> bool {
+        !self.actors.is_empty() || self.lights.as_ref().is_some_and(Lightmap::flickers)
+    }
+
+    /// Steps the world `dt` seconds on.
+    pub fn update(&mut self, dt: f32, input: &Input) {
+        self.time += dt;
+        let cam = &mut self.camera;
+        if (cam.x, cam.y) != (self.pos.0.round() as i32, self.pos.1.round() as i32) {
+            // someone moved the camera by hand
+            self.pos = (cam.x as f32, cam.y as f32);
+        }
+        match input.zoom {
+            Some(Zoom::In) => cam.zoom(1.25),
+            Some(Zoom::Out) => cam.zoom(0.8),
+            Some(Zoom::Reset) => cam.set_fov(self.start_fov),
+            Some(Zoom::Nearer) => cam.set_max_distance(cam.max_distance * 0.8),
+            Some(Zoom::Further) => cam.set_max_distance(cam.max_distance * 1.25),
+            None => {}
+        }
+        let turn = input.turn_left as i32 - input.turn_right as i32;
+        cam.radians += turn as f32 * self.turn_speed * dt;
+        let walk = input.forward as i32 - input.back as i32;
+        let step = walk as f32 * self.move_speed * dt;
+        self.pos.0 += step * cam.radians.cos();
+        self.pos.1 -= step * cam.radians.sin(); // minus because +y is down
+        (cam.x, cam.y) = (self.pos.0.round() as i32, self.pos.1.round() as i32);
+
+        if let Some(lights) = &mut self.lights {
+            lights.update(self.time);
+        }
+        let player = (self.camera.x, self.camera.y);
+        for actor in &mut self.actors {
+            actor.update(&self.map, &self.nav, player, dt);
+        }
+    }
+
+    /// The first wall, thing or actor along `trace`. Enemies are hit where
+    /// their actors have got to, not where the map put them.
+    pub fn trace(&self, trace: &Trace) -> Option<TraceHit> {
+        let hit = trace.cast_filtered(&self.map, |thing| thing.kind != ThingKind::Enemy);
+        let reach = hit.map_or(trace.range, |hit| hit.distance);
+        let actor = self
+            .actors
+            .iter()
+            .enumerate()
+            .filter_map(|(i, actor)| trace.circle_hit((actor.x, actor.y), Target::Actor(i)))
+            .filter(|hit| hit.distance < reach)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance));
+        actor.or(hit)
+    }
+
+    /// The 3D view from the camera, in palette indices.
+    pub fn render(&self, frame: &mut IndexedImage) {
+        let shading = Shading {
+            lights: self.lights.as_ref(),
+            ..self.shading
+        };
+        let view = cast::cast_columns(&self.map, &self.camera, frame.width());
+        draw::draw_view_indexed_shaded(frame, &view, &self.camera, &shading);
+        let billboards: Vec<_> = self
+            .actors
+            .iter()
+            .map(|a| a.billboard(&self.camera, &self.sprites))
+            .collect();
+        sprite::draw_billboards_indexed(frame, &view, &self.camera, &billboards);
+    }
+
+    /// A top-down view: the map, the camera and its rays, and a dot for
+    /// each actor. `img` should be the map's size.
+    pub fn render_map(&self, img: &mut impl Frame) {
+        draw::draw_map(img, &self.map);
+        draw::draw_camera(img, &self.camera);
+        let (w, h) = img.dimensions();
+        for actor in &self.actors {
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let (x, y) = (actor.x as u32 + dx, actor.y as u32 + dy);
+                if x < w && y < h {
+                    img.put(x, y, image::Rgb([187, 127, 87]));
+                }
+            }
+        }
+        let view = cast::cast_fov(&self.map, &self.camera);
+        draw::draw_fov(img, &view, &self.camera);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{self, Segment, Wall};
+
+    #[test]
+    fn update() {
+        let mut world = World::new(map::spooky_map());
+        assert_eq!((world.camera.x, world.camera.y), (240, 464));
+        assert_eq!(world.actors.len(), 1);
+        assert!(world.animates());
+
+        // the spawn faces up the map, so walking forward takes y down
+        let forward = Input {
+            forward: true,
+            ..Input::default()
+        };
+        for _ in 0..10 {
+            world.update(0.01, &forward);
+        }
+        assert_eq!((world.camera.x, world.camera.y), (240, 448));
+        assert!((world.time - 0.1).abs() < 1e-6);
+        let left = Input {
+            turn_left: true,
+            ..Input::default()
+        };
+        world.update(0.5, &left);
+        assert!((world.camera.radians - PI).abs() < 1e-6);
+
+        // moving the camera by hand sticks
+        world.camera.x = 300;
+        world.update(0.1, &Input::default());
+        assert_eq!(world.camera.x, 300);
+
+        // zooming happens once a press, and resets to where it started
+        let zoom = |zoom| Input {
+            zoom: Some(zoom),
+            ..Input::default()
+        };
+        world.update(0.1, &zoom(Zoom::Out));
+        world.update(0.1, &Input::default());
+        assert!(world.camera.fov > world.start_fov);
+        world.update(0.1, &zoom(Zoom::Reset));
+        assert_eq!(world.camera.fov, world.start_fov);
+        world.update(0.1, &zoom(Zoom::Further));
+        assert_eq!(world.camera.max_distance, 640.0);
+    }
+
+    #[test]
+    fn trace() {
+        // an imp that has walked off its spawn point is hit where it is now
+        let mut map = Map::new(128, 64);
+        map.add_thing(ThingKind::Enemy, "imp", 80, 32);
+        let mut world = World::new(map);
+        let shot = Trace {
+            x: 20.0,
+            y: 32.0,
+            thing_radius: 4.0,
+            ..Trace::default()
+        };
+        assert_eq!(world.trace(&shot).unwrap().target, Target::Actor(0));
+        (world.actors[0].x, world.actors[0].y) = (80.0, 50.0);
+        assert_eq!(world.trace(&shot), None);
+        let low = Trace { y: 50.0, ..shot };
+        let hit = world.trace(&low).unwrap();
+        assert_eq!(hit.target, Target::Actor(0));
+        assert!((hit.distance - 56.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn render() {
+        let mut world = World::new(map::spooky_map());
+        world.actors.clear();
+        let mut frame = IndexedImage::new(320, 200);
+        world.render(&mut frame);
+
+        let view = cast::cast_columns(&world.map, &world.camera, 320);
+        let shading = Shading {
+            lights: world.lights.as_ref(),
+            ..Shading::default()
+        };
+        let mut expected = IndexedImage::new(320, 200);
+        draw::draw_view_indexed_shaded(&mut expected, &view, &world.camera, &shading);
+        assert!(frame == expected);
+    }
+
+    #[test]
+    fn render_map_with_segments() {
+        // rays stopped by a segment end between steps, and the debug rays
+        // have to stop there too rather than run off the top of the map
+        let mut map = map::spooky_map();
+        map.segments
+            .push(Segment::new((200.0, 30.0), (260.0, 40.0), Wall::Brick));
+        let mut world = World::new(map);
+        (world.camera.x, world.camera.y) = (230, 48);
+        world.camera.radians = PI / 2.0;
+        let mut img = image::RgbImage::new(world.map.w, world.map.h);
+        world.render_map(&mut img);
+        let mut bare = image::RgbImage::new(world.map.w, world.map.h);
+        draw::draw_map(&mut bare, &world.map);
+        assert_ne!(img.get(230, 40), bare.get(230, 40));
+        assert_eq!(img.get(230, 20), bare.get(230, 20));
+    }
+
+    #[test]
+    fn colored_lights() {
+        // the same torches in white and in red come out differently
+        let mut white = map::spooky_map();
+        for light in &mut white.lights {
+            light.color = image::Rgb([255, 255, 255]);
+        }
+        let mut red = map::spooky_map();
+        for light in &mut red.lights {
+            light.color = image::Rgb([255, 0, 0]);
+        }
+        let frames = [white, red].map(|map| {
+            let mut world = World::new(map);
+            let mut frame = IndexedImage::new(160, 100);
+            world.actors.clear();
+            world.render(&mut frame);
+            frame
+        });
+        assert!(frames[0] != frames[1]);
+    }
+}